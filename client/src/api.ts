@@ -113,9 +113,25 @@ export async function updateProfile(displayName?: string, bio?: string) {
 	});
 }
 
+// List endpoints return { items, next_cursor, prev_cursor }
+interface Page<T> {
+	items: T[];
+	next_cursor: string | null;
+	prev_cursor: string | null;
+}
+
+function pageQuery(before?: string, limit?: number) {
+	const params = new URLSearchParams();
+	if (before) params.set("before", before);
+	if (limit) params.set("limit", String(limit));
+	const qs = params.toString();
+	return qs ? `?${qs}` : "";
+}
+
 // Chat
-export async function getChatHistory() {
-	return request("/chat");
+export async function getChatHistory(before?: string) {
+	const page: Page<unknown> = await request(`/chat${pageQuery(before)}`);
+	return page.items;
 }
 
 export async function sendMessage(content: string) {
@@ -139,13 +155,15 @@ export async function triggerMatching() {
 	return request("/matching/trigger", { method: "POST" });
 }
 
-export async function getMatches() {
-	return request("/matches");
+export async function getMatches(before?: string) {
+	const page: Page<unknown> = await request(`/matches${pageQuery(before)}`);
+	return page.items;
 }
 
 // Notifications
-export async function getNotifications() {
-	return request("/notifications");
+export async function getNotifications(before?: string) {
+	const page: Page<unknown> = await request(`/notifications${pageQuery(before)}`);
+	return page.items;
 }
 
 export async function getUnreadCount() {
@@ -157,8 +175,11 @@ export async function markNotificationRead(id: number) {
 }
 
 // Direct Messages
-export async function getDirectMessages(matchId: number) {
-	return request(`/messages/${matchId}`);
+export async function getDirectMessages(matchId: number, before?: string) {
+	const page: Page<unknown> = await request(
+		`/messages/${matchId}${pageQuery(before)}`,
+	);
+	return page.items;
 }
 
 export async function sendDirectMessage(matchId: number, content: string) {
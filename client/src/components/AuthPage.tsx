@@ -46,13 +46,15 @@ export default function AuthPage({ onLogin }: AuthPageProps) {
 
 				<form onSubmit={handleSubmit}>
 					<div className="form-group">
-						<label htmlFor="username">Username</label>
+						<label htmlFor="username">
+							{isRegister ? "Username" : "Username or email"}
+						</label>
 						<input
 							id="username"
 							type="text"
 							value={username}
 							onChange={(e) => setUsername(e.target.value)}
-							placeholder="Choose a username"
+							placeholder={isRegister ? "Choose a username" : "Username or email"}
 							required
 							autoComplete="username"
 						/>
@@ -0,0 +1,654 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::agent::LlmAgent;
+use crate::attachments::{detach_message_attachments, remove_blobs};
+use crate::auth::require_admin;
+use crate::broker::Broker;
+use crate::db::Database;
+use crate::models::*;
+use crate::realtime::publish_to_match;
+use crate::reports::{case_from_row, record_case_event, CASE_COLUMNS};
+use crate::routes::{create_notification, get_agent_profile_db, get_direct_message_db, run_matching, update_user_profile_bg};
+use crate::storage::BlobStore;
+
+const ADMIN_USER_COLUMNS: &str = "u.id, u.username, u.email, u.display_name, u.role, u.created_at, u.suspended_at, u.suspension_reason, u.banned_at, u.deletion_requested_at,
+    (SELECT COUNT(*) FROM conversations c WHERE c.user_id = u.id),
+    (SELECT COUNT(*) FROM matches m WHERE m.is_matched = 1 AND (m.user_a_id = u.id OR m.user_b_id = u.id))";
+
+fn admin_user_from_row(row: &rusqlite::Row) -> rusqlite::Result<AdminUserSummary> {
+    Ok(AdminUserSummary {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        email: row.get(2)?,
+        display_name: row.get(3)?,
+        role: row.get(4)?,
+        created_at: row.get(5)?,
+        suspended_at: row.get(6)?,
+        suspension_reason: row.get(7)?,
+        banned_at: row.get(8)?,
+        deletion_requested_at: row.get(9)?,
+        chat_message_count: row.get(10)?,
+        confirmed_match_count: row.get(11)?,
+    })
+}
+
+/// Promote the usernames listed in `ADMIN_USERNAMES` (comma-separated) so a fresh deployment has an admin
+pub fn bootstrap_admins(db: &Database) {
+    let usernames = std::env::var("ADMIN_USERNAMES").unwrap_or_default();
+    let conn = db.conn.lock().unwrap();
+    for username in usernames.split(',').map(str::trim).filter(|u| !u.is_empty()) {
+        match conn.execute(
+            "UPDATE users SET role = 'admin' WHERE username = ?1 COLLATE NOCASE AND role != 'admin'",
+            rusqlite::params![username],
+        ) {
+            Ok(n) if n > 0 => log::info!("👮 Promoted {} to admin", username),
+            Ok(_) => {}
+            Err(e) => log::error!("Admin bootstrap failed for {}: {}", username, e),
+        }
+    }
+}
+
+// ── Users ──
+
+pub async fn list_users(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<AdminUserQuery>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let pattern = format!("%{}%", query.q.clone().unwrap_or_default().trim());
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let conn = db.conn.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM users u
+             WHERE u.deleted_at IS NULL AND (u.username LIKE ?1 OR u.email LIKE ?1 OR u.display_name LIKE ?1 OR u.id = ?2)
+             ORDER BY u.created_at DESC LIMIT ?3 OFFSET ?4",
+            ADMIN_USER_COLUMNS
+        ))
+        .unwrap();
+
+    let users: Vec<AdminUserSummary> = stmt
+        .query_map(
+            rusqlite::params![&pattern, query.q.as_deref().unwrap_or_default().trim(), limit, offset],
+            admin_user_from_row,
+        )
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(users)
+}
+
+pub async fn get_user(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let user_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+
+    let user = match conn.query_row(
+        &format!("SELECT {} FROM users u WHERE u.id = ?1", ADMIN_USER_COLUMNS),
+        rusqlite::params![&user_id],
+        admin_user_from_row,
+    ) {
+        Ok(u) => u,
+        Err(_) => return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"})),
+    };
+
+    let mut stmt = conn
+        .prepare("SELECT id, user_a_id, user_b_id, agent_a_approves, agent_b_approves, is_matched, created_at, updated_at FROM matches WHERE user_a_id = ?1 OR user_b_id = ?1 ORDER BY updated_at DESC")
+        .unwrap();
+    let matches: Vec<MatchRecord> = stmt
+        .query_map(rusqlite::params![&user_id], |row| {
+            Ok(MatchRecord {
+                id: row.get(0)?,
+                user_a_id: row.get(1)?,
+                user_b_id: row.get(2)?,
+                agent_a_approves: row.get::<_, i32>(3)? != 0,
+                agent_b_approves: row.get::<_, i32>(4)? != 0,
+                is_matched: row.get::<_, i32>(5)? != 0,
+                created_at: row.get(6)?,
+                updated_at: row.get(7)?,
+                other_user: None,
+                unread_count: 0,
+                last_message: None,
+                other_last_read_message_id: 0,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(AdminUserDetail {
+        agent_profile: get_agent_profile_db(&conn, &user_id),
+        user,
+        matches,
+    })
+}
+
+pub async fn suspend_user(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    body: web::Json<SuspendUserRequest>,
+) -> HttpResponse {
+    let claims = match require_admin(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let user_id = path.into_inner();
+    if user_id == claims.sub {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "You cannot suspend yourself"}));
+    }
+
+    let conn = db.conn.lock().unwrap();
+    let updated = conn
+        .execute(
+            "UPDATE users SET suspended_at = datetime('now'), suspension_reason = ?1, updated_at = datetime('now') WHERE id = ?2 AND deleted_at IS NULL",
+            rusqlite::params![&body.reason, &user_id],
+        )
+        .unwrap_or(0);
+
+    if updated == 0 {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"}));
+    }
+
+    log::info!("Admin {} suspended user {}", claims.sub, user_id);
+    HttpResponse::Ok().json(serde_json::json!({"status": "suspended"}))
+}
+
+pub async fn unsuspend_user(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let claims = match require_admin(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let user_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let updated = conn
+        .execute(
+            "UPDATE users SET suspended_at = NULL, suspension_reason = NULL, banned_at = NULL, updated_at = datetime('now') WHERE id = ?1 AND deleted_at IS NULL",
+            rusqlite::params![&user_id],
+        )
+        .unwrap_or(0);
+
+    if updated == 0 {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"}));
+    }
+
+    log::info!("Admin {} unsuspended user {}", claims.sub, user_id);
+    HttpResponse::Ok().json(serde_json::json!({"status": "active"}))
+}
+
+pub async fn set_user_role(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    body: web::Json<SetRoleRequest>,
+) -> HttpResponse {
+    let claims = match require_admin(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    if body.role != "user" && body.role != "admin" {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "role must be 'user' or 'admin'"}));
+    }
+
+    let user_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let updated = conn
+        .execute(
+            "UPDATE users SET role = ?1, updated_at = datetime('now') WHERE id = ?2 AND deleted_at IS NULL",
+            rusqlite::params![&body.role, &user_id],
+        )
+        .unwrap_or(0);
+
+    if updated == 0 {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"}));
+    }
+
+    log::info!("Admin {} set role of {} to {}", claims.sub, user_id, body.role);
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok", "role": body.role}))
+}
+
+// ── Agent & Matching ──
+
+pub async fn rebuild_profile(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    agent: web::Data<LlmAgent>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let user_id = path.into_inner();
+    let db_clone = db.clone();
+    let agent_clone = agent.clone();
+    tokio::spawn(async move {
+        update_user_profile_bg(db_clone, agent_clone, user_id).await;
+    });
+
+    HttpResponse::Ok().json(serde_json::json!({"status": "Profile rebuild triggered"}))
+}
+
+pub async fn rerun_matching(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    agent: web::Data<LlmAgent>,
+    broker: web::Data<dyn Broker>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let user_id = path.into_inner();
+    match run_matching(&db, &agent, broker.get_ref(), &user_id).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({"error": e})),
+    }
+}
+
+pub async fn matching_stats(
+    req: HttpRequest,
+    db: web::Data<Database>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let conn = db.conn.lock().unwrap();
+    let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap_or(0);
+
+    let stats = MatchingStats {
+        total_users: count("SELECT COUNT(*) FROM users WHERE deleted_at IS NULL"),
+        suspended_users: count("SELECT COUNT(*) FROM users WHERE deleted_at IS NULL AND suspended_at IS NOT NULL"),
+        profiled_users: count("SELECT COUNT(*) FROM agent_profiles WHERE personality_summary != '' OR interests != ''"),
+        peer_evaluations: count("SELECT COUNT(*) FROM agent_peer_notes"),
+        recommendations: count("SELECT COUNT(*) FROM agent_peer_notes WHERE recommends_match = 1"),
+        average_compatibility: conn
+            .query_row("SELECT COALESCE(AVG(compatibility_score), 0.0) FROM agent_peer_notes", [], |row| row.get(0))
+            .unwrap_or(0.0),
+        pending_proposals: count("SELECT COUNT(*) FROM matches WHERE is_matched = 0"),
+        confirmed_matches: count("SELECT COUNT(*) FROM matches WHERE is_matched = 1"),
+        direct_messages: count("SELECT COUNT(*) FROM direct_messages"),
+    };
+
+    HttpResponse::Ok().json(stats)
+}
+
+// ── Moderation ──
+
+const MODERATION_FLAG_COLUMNS: &str = "f.id, f.user_id, COALESCE(u.username, ''), f.context, f.match_id, f.message_id, f.content, f.action, f.category, f.reason, f.source, f.status, f.reviewed_by, f.review_note, f.reviewed_at, f.created_at";
+
+fn moderation_flag_from_row(row: &rusqlite::Row) -> rusqlite::Result<ModerationFlag> {
+    Ok(ModerationFlag {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        username: row.get(2)?,
+        context: row.get(3)?,
+        match_id: row.get(4)?,
+        message_id: row.get(5)?,
+        content: row.get(6)?,
+        action: row.get(7)?,
+        category: row.get(8)?,
+        reason: row.get(9)?,
+        source: row.get(10)?,
+        status: row.get(11)?,
+        reviewed_by: row.get(12)?,
+        review_note: row.get(13)?,
+        reviewed_at: row.get(14)?,
+        created_at: row.get(15)?,
+    })
+}
+
+pub async fn list_moderation_flags(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<ModerationFlagQuery>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let status = query.status.clone().unwrap_or_else(|| "pending".to_string());
+    if !["pending", "dismissed", "upheld", "all"].contains(&status.as_str()) {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "status must be pending, dismissed, upheld or all"}));
+    }
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let conn = db.conn.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM moderation_flags f LEFT JOIN users u ON u.id = f.user_id
+             WHERE ?1 = 'all' OR f.status = ?1
+             ORDER BY f.id DESC LIMIT ?2 OFFSET ?3",
+            MODERATION_FLAG_COLUMNS
+        ))
+        .unwrap();
+
+    let flags: Vec<ModerationFlag> = stmt
+        .query_map(rusqlite::params![&status, limit, offset], moderation_flag_from_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(flags)
+}
+
+pub async fn review_moderation_flag(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    blobs: web::Data<dyn BlobStore>,
+    path: web::Path<i64>,
+    body: web::Json<ReviewFlagRequest>,
+) -> HttpResponse {
+    let claims = match require_admin(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let status = match body.decision.as_str() {
+        "dismiss" => "dismissed",
+        "uphold" => "upheld",
+        _ => return HttpResponse::BadRequest().json(serde_json::json!({"error": "decision must be 'dismiss' or 'uphold'"})),
+    };
+
+    let flag_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let flag = match conn.query_row(
+        &format!("SELECT {} FROM moderation_flags f LEFT JOIN users u ON u.id = f.user_id WHERE f.id = ?1", MODERATION_FLAG_COLUMNS),
+        rusqlite::params![flag_id],
+        moderation_flag_from_row,
+    ) {
+        Ok(f) => f,
+        Err(_) => return HttpResponse::NotFound().json(serde_json::json!({"error": "Flag not found"})),
+    };
+
+    conn.execute(
+        "UPDATE moderation_flags SET status = ?1, reviewed_by = ?2, review_note = ?3, reviewed_at = datetime('now') WHERE id = ?4",
+        rusqlite::params![status, &claims.sub, &body.note, flag_id],
+    ).unwrap();
+
+    // Upholding a DM flag can take the message down for both participants
+    if status == "upheld"
+        && body.remove_content.unwrap_or(false)
+        && flag.context == "dm"
+        && let (Some(match_id), Some(message_id)) = (flag.match_id, flag.message_id)
+    {
+        let blob_keys = detach_message_attachments(&conn, "id = ?1", &message_id).unwrap_or_default();
+        conn.execute(
+            "UPDATE direct_messages SET deleted_at = COALESCE(deleted_at, datetime('now')) WHERE id = ?1",
+            rusqlite::params![message_id],
+        ).unwrap();
+        remove_blobs(blobs.get_ref(), &blob_keys);
+
+        let participants = conn.query_row(
+            "SELECT user_a_id, user_b_id FROM matches WHERE id = ?1",
+            rusqlite::params![match_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        );
+        if let (Ok(participants), Some(msg)) = (participants, get_direct_message_db(&conn, match_id, message_id)) {
+            publish_to_match(broker.get_ref(), &participants, RealtimeEvent::MessageUpdated { message: msg });
+        }
+    }
+
+    log::info!("Admin {} {} moderation flag {}", claims.sub, status, flag_id);
+    HttpResponse::Ok().json(serde_json::json!({"status": status}))
+}
+
+// ── Cases ──
+
+fn load_case(conn: &rusqlite::Connection, case_id: i64) -> Option<ModerationCase> {
+    conn.query_row(
+        &format!("SELECT {} FROM moderation_cases WHERE id = ?1", CASE_COLUMNS),
+        rusqlite::params![case_id],
+        case_from_row,
+    )
+    .ok()
+}
+
+pub async fn list_cases(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<CaseQuery>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let status = query.status.clone().unwrap_or_else(|| "active".to_string());
+    if !["active", "open", "triaged", "actioned", "closed", "all"].contains(&status.as_str()) {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "status must be active, open, triaged, actioned, closed or all"}));
+    }
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let conn = db.conn.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM moderation_cases
+             WHERE ?1 = 'all' OR (?1 = 'active' AND status != 'closed') OR status = ?1
+             ORDER BY CASE priority WHEN 'high' THEN 0 WHEN 'normal' THEN 1 ELSE 2 END, id ASC
+             LIMIT ?2 OFFSET ?3",
+            CASE_COLUMNS
+        ))
+        .unwrap();
+
+    let cases: Vec<ModerationCase> = stmt
+        .query_map(rusqlite::params![&status, limit, offset], case_from_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(cases)
+}
+
+pub async fn get_case(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(&req) {
+        return e;
+    }
+
+    let case_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let Some(case) = load_case(&conn, case_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Case not found"}));
+    };
+
+    let mut stmt = conn
+        .prepare("SELECT id, case_id, actor_id, event_type, details, created_at FROM moderation_case_events WHERE case_id = ?1 ORDER BY id ASC")
+        .unwrap();
+    let events: Vec<ModerationCaseEvent> = stmt
+        .query_map(rusqlite::params![case_id], |row| {
+            Ok(ModerationCaseEvent {
+                id: row.get(0)?,
+                case_id: row.get(1)?,
+                actor_id: row.get(2)?,
+                event_type: row.get(3)?,
+                details: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(ModerationCaseDetail { case, events })
+}
+
+pub async fn triage_case(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+    body: web::Json<TriageCaseRequest>,
+) -> HttpResponse {
+    let claims = match require_admin(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    if let Some(p) = &body.priority
+        && !["low", "normal", "high"].contains(&p.as_str())
+    {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "priority must be low, normal or high"}));
+    }
+
+    let case_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let Some(case) = load_case(&conn, case_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Case not found"}));
+    };
+    if case.status == "closed" {
+        return HttpResponse::Conflict().json(serde_json::json!({"error": "Case is closed"}));
+    }
+
+    let priority = body.priority.clone().unwrap_or(case.priority);
+    // Triage claims the case; an already actioned case keeps its status
+    conn.execute(
+        "UPDATE moderation_cases SET priority = ?1, assigned_to = ?2, status = CASE WHEN status = 'open' THEN 'triaged' ELSE status END, updated_at = datetime('now') WHERE id = ?3",
+        rusqlite::params![&priority, &claims.sub, case_id],
+    ).unwrap();
+    let details = match &body.note {
+        Some(note) if !note.trim().is_empty() => format!("priority {}: {}", priority, note.trim()),
+        _ => format!("priority {}", priority),
+    };
+    let _ = record_case_event(&conn, case_id, Some(&claims.sub), "triaged", &details);
+
+    log::info!("Admin {} triaged case {} as {}", claims.sub, case_id, priority);
+    match load_case(&conn, case_id) {
+        Some(case) => HttpResponse::Ok().json(case),
+        None => HttpResponse::NotFound().json(serde_json::json!({"error": "Case not found"})),
+    }
+}
+
+pub async fn case_action(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    path: web::Path<i64>,
+    body: web::Json<CaseActionRequest>,
+) -> HttpResponse {
+    let claims = match require_admin(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let event_type = match body.action.as_str() {
+        "warn" => "warned",
+        "suspend" => "suspended",
+        "ban" => "banned",
+        _ => return HttpResponse::BadRequest().json(serde_json::json!({"error": "action must be warn, suspend or ban"})),
+    };
+    let note = body.note.clone().unwrap_or_default().trim().to_string();
+
+    let case_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let Some(case) = load_case(&conn, case_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Case not found"}));
+    };
+    if case.status == "closed" {
+        return HttpResponse::Conflict().json(serde_json::json!({"error": "Case is closed"}));
+    }
+    if case.reported_user_id == claims.sub {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "You cannot action a case against yourself"}));
+    }
+
+    let applied = match body.action.as_str() {
+        "warn" => {
+            let message = if note.is_empty() {
+                "A report about your recent activity was upheld. Please review the community guidelines.".to_string()
+            } else {
+                note.clone()
+            };
+            create_notification(&conn, broker.get_ref(), &case.reported_user_id, "moderation_warning", "Warning from moderators", &message, None)
+                .map(|_| 1)
+                .unwrap_or(0)
+        }
+        action => {
+            let reason = if note.is_empty() { format!("Report #{}: {}", case_id, case.reason) } else { note.clone() };
+            let banned = if action == "ban" { "datetime('now')" } else { "banned_at" };
+            conn.execute(
+                &format!(
+                    "UPDATE users SET suspended_at = COALESCE(suspended_at, datetime('now')), suspension_reason = ?1, banned_at = {}, updated_at = datetime('now') WHERE id = ?2 AND deleted_at IS NULL",
+                    banned
+                ),
+                rusqlite::params![&reason, &case.reported_user_id],
+            )
+            .unwrap_or(0)
+        }
+    };
+    if applied == 0 {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Reported user no longer exists"}));
+    }
+
+    conn.execute(
+        "UPDATE moderation_cases SET status = 'actioned', assigned_to = COALESCE(assigned_to, ?1), updated_at = datetime('now') WHERE id = ?2",
+        rusqlite::params![&claims.sub, case_id],
+    ).unwrap();
+    let _ = record_case_event(&conn, case_id, Some(&claims.sub), event_type, &note);
+
+    log::info!("Admin {} {} user {} on case {}", claims.sub, event_type, case.reported_user_id, case_id);
+    match load_case(&conn, case_id) {
+        Some(case) => HttpResponse::Ok().json(case),
+        None => HttpResponse::NotFound().json(serde_json::json!({"error": "Case not found"})),
+    }
+}
+
+pub async fn close_case(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+    body: web::Json<CloseCaseRequest>,
+) -> HttpResponse {
+    let claims = match require_admin(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let resolution = body.resolution.clone().unwrap_or_default().trim().to_string();
+    let case_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let Some(case) = load_case(&conn, case_id) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Case not found"}));
+    };
+    if case.status == "closed" {
+        return HttpResponse::Conflict().json(serde_json::json!({"error": "Case is already closed"}));
+    }
+
+    conn.execute(
+        "UPDATE moderation_cases SET status = 'closed', resolution = ?1, closed_at = datetime('now'), updated_at = datetime('now') WHERE id = ?2",
+        rusqlite::params![&resolution, case_id],
+    ).unwrap();
+    let _ = record_case_event(&conn, case_id, Some(&claims.sub), "closed", &resolution);
+
+    log::info!("Admin {} closed case {}", claims.sub, case_id);
+    match load_case(&conn, case_id) {
+        Some(case) => HttpResponse::Ok().json(case),
+        None => HttpResponse::NotFound().json(serde_json::json!({"error": "Case not found"})),
+    }
+}
@@ -1,29 +1,79 @@
 use reqwest::Client;
-use serde_json::json;
 
+use crate::budget::TokenBudget;
 use crate::models::*;
+use crate::prompts::{PromptTemplates, RenderedPrompt};
+use crate::redaction::{PiiVault, Redactor};
+use crate::visibility::{profile_field, profile_field_mut, PROFILE_FIELDS};
 
 pub struct LlmAgent {
     client: Client,
     base_url: String,
     model: String,
     api_key: String,
+    redactor: Redactor,
+    budget: TokenBudget,
+    prompts: PromptTemplates,
 }
 
 impl LlmAgent {
     pub fn new() -> Self {
+        let model = std::env::var("LLM_MODEL")
+            .unwrap_or_else(|_| "llama3".to_string());
         LlmAgent {
             client: Client::new(),
             base_url: std::env::var("LLM_BASE_URL")
                 .unwrap_or_else(|_| "http://localhost:11434/v1".to_string()),
-            model: std::env::var("LLM_MODEL")
-                .unwrap_or_else(|_| "llama3".to_string()),
             api_key: std::env::var("LLM_API_KEY")
                 .unwrap_or_else(|_| "not-needed".to_string()),
+            redactor: Redactor::from_env(),
+            budget: TokenBudget::for_model(&model),
+            prompts: PromptTemplates::from_env(),
+            model,
         }
     }
 
-    async fn call_llm(&self, messages: Vec<LlmMessage>, temperature: f64, max_tokens: u32) -> Result<String, String> {
+    /// `profile` with its fields cut down to share `max_tokens` between them
+    fn fit_profile(&self, profile: &AgentProfile, max_tokens: usize) -> AgentProfile {
+        let values: Vec<&str> = PROFILE_FIELDS.iter().map(|f| profile_field(profile, f)).collect();
+        let fitted = self.budget.fit_fields(&values, max_tokens);
+        let mut out = profile.clone();
+        for (field, value) in PROFILE_FIELDS.iter().zip(fitted) {
+            if let Some(slot) = profile_field_mut(&mut out, field) {
+                *slot = value;
+            }
+        }
+        out
+    }
+
+    /// Call the LLM with personal data redacted, including `known_names` of the people in the
+    /// prompt. The reply keeps its placeholders, which is what we want for anything that gets
+    /// stored or shown to someone else.
+    async fn call_llm(&self, messages: Vec<LlmMessage>, known_names: &[String], temperature: f64, max_tokens: u32) -> Result<String, String> {
+        self.call_llm_redacted(messages, known_names, temperature, max_tokens).await.map(|(reply, _)| reply)
+    }
+
+    /// Like `call_llm`, but puts the user's own details back into the reply. Only for replies
+    /// that go straight back to the user who wrote them.
+    async fn call_llm_restoring(&self, messages: Vec<LlmMessage>, known_names: &[String], temperature: f64, max_tokens: u32) -> Result<String, String> {
+        let (reply, vault) = self.call_llm_redacted(messages, known_names, temperature, max_tokens).await?;
+        Ok(vault.restore(&reply))
+    }
+
+    async fn call_llm_redacted(
+        &self,
+        mut messages: Vec<LlmMessage>,
+        known_names: &[String],
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<(String, PiiVault), String> {
+        let vault = self.redactor.redact_messages(&mut messages, known_names);
+        if !vault.is_empty()
+            && let Some(system) = messages.iter_mut().find(|m| m.role == "system")
+        {
+            system.content.push_str("\n\nSome personal details have been replaced with placeholders like [EMAIL_1] or [NAME_1]. Treat them as the real values and copy them exactly if you need to refer to them.");
+        }
+
         let request = LlmRequest {
             model: self.model.clone(),
             messages,
@@ -58,132 +108,164 @@ impl LlmAgent {
         llm_response
             .choices
             .first()
-            .map(|c| c.message.content.clone())
+            .map(|c| (c.message.content.clone(), vault))
             .ok_or_else(|| "No response from LLM".to_string())
     }
 
-    /// Chat with user — the personal agent conversation
+    /// Chat with user — the personal agent conversation.
+    /// Returns the reply and the version of the prompt template that produced it.
+    #[allow(clippy::too_many_arguments)]
     pub async fn chat_with_user(
         &self,
         history: &[ChatMessage],
         agent_profile: &AgentProfile,
+        persona: &AgentPersona,
+        onboarding: &OnboardingContext,
+        memory: &ConversationMemory,
         user_message: &str,
-    ) -> Result<String, String> {
-        let system_prompt = format!(
-            r#"You are Jupiter, a warm, empathetic AI companion. Your job is to get to know your user deeply — their personality, interests, values, dreams, what they're looking for in a partner, and their daily life.
-
-You should be conversational, curious, and genuinely interested. Ask thoughtful follow-up questions. Remember everything they tell you.
-
-Current knowledge about this user:
-- Personality: {}
-- Interests: {}
-- Core Values: {}
-- Communication style: {}
-- Looking for in a partner: {}
-- Deal breakers: {}
-- Additional notes: {}
-
-Guidelines:
-1. If this is a new user (empty profile), start by warmly welcoming them and asking about themselves
-2. Be natural — don't interrogate. Have a real conversation
-3. Periodically ask about what they're looking for in a partner
-4. Remember and reference things they've told you before
-5. Be supportive, positive, but honest
-6. Keep responses concise but warm (2-4 paragraphs max)"#,
-            if agent_profile.personality_summary.is_empty() { "Not yet known" } else { &agent_profile.personality_summary },
-            if agent_profile.interests.is_empty() { "Not yet known" } else { &agent_profile.interests },
-            if agent_profile.core_values.is_empty() { "Not yet known" } else { &agent_profile.core_values },
-            if agent_profile.communication_style.is_empty() { "Not yet known" } else { &agent_profile.communication_style },
-            if agent_profile.looking_for.is_empty() { "Not yet known" } else { &agent_profile.looking_for },
-            if agent_profile.deal_breakers.is_empty() { "Not yet known" } else { &agent_profile.deal_breakers },
-            if agent_profile.raw_notes.is_empty() { "None yet" } else { &agent_profile.raw_notes },
+        known_names: &[String],
+    ) -> Result<(String, String), String> {
+        let render = |profile: &AgentProfile, memory: &str, earlier: &str| {
+            self.prompts.render(
+                "chat",
+                minijinja::context! { persona, onboarding, profile, memory, earlier },
+            )
+        };
+
+        // The user's new message is kept whole unless it alone would crowd out everything else
+        let fixed = render(&AgentProfile::default(), "", "")?.system;
+        let user_message = self.budget.truncate(user_message, self.budget.available(&[&fixed], 1024) / 2);
+        let available = self.budget.available(&[&fixed, &user_message], 1024);
+
+        let memory_text = format_memory(memory);
+        let transcript: Vec<String> = history.iter().map(|m| format!("{}: {}", m.role, m.content)).collect();
+        let profile_needs = PROFILE_FIELDS.iter().map(|f| self.budget.count(profile_field(agent_profile, f))).sum();
+        let grants = self.budget.allocate(
+            available,
+            &[profile_needs, self.budget.count(&memory_text), self.budget.items_cost(&transcript)],
+            &[2, 2, 5],
         );
 
+        let (kept, earlier) = self.budget.fit_recent(&transcript, grants[2]);
+        let RenderedPrompt { system, version, .. } = render(
+            &self.fit_profile(agent_profile, grants[0]),
+            &self.budget.truncate(&memory_text, grants[1]),
+            &earlier,
+        )?;
+
         let mut messages = vec![LlmMessage {
             role: "system".to_string(),
-            content: system_prompt,
+            content: system,
         }];
 
-        // Add recent conversation history (last 20 messages for context)
-        for msg in history.iter().rev().take(20).rev() {
+        // As much recent conversation history as the budget allows
+        for (msg, line) in history[history.len() - kept.len()..].iter().zip(&kept) {
+            let prefix = format!("{}: ", msg.role);
             messages.push(LlmMessage {
                 role: msg.role.clone(),
-                content: msg.content.clone(),
+                content: line.strip_prefix(&prefix).unwrap_or(line).to_string(),
             });
         }
 
         messages.push(LlmMessage {
             role: "user".to_string(),
-            content: user_message.to_string(),
+            content: user_message,
         });
 
-        self.call_llm(messages, 0.8, 1024).await
+        let reply = self.call_llm_restoring(messages, known_names, 0.8, 1024).await?;
+        Ok((reply, version))
+    }
+
+    /// Fold older chat turns into the running summary of everything the user has told their agent.
+    /// Takes as many of `messages` as fit, oldest first, and returns the summary with that count
+    /// and the version of the prompt template that produced it.
+    pub async fn summarize_conversation(
+        &self,
+        previous_summary: &str,
+        messages: &[ChatMessage],
+        known_names: &[String],
+    ) -> Result<(String, usize, String), String> {
+        let render = |summary: &str, transcript: &str| {
+            self.prompts.render("summary", minijinja::context! { summary, transcript })
+        };
+
+        let fixed = render("", "")?;
+        let available = self.budget.available(&[&fixed.system, &fixed.prompt], 768);
+        let lines: Vec<String> = messages.iter().map(|m| format!("{}: {}", m.role, m.content)).collect();
+        let grants = self.budget.allocate(
+            available,
+            &[self.budget.count(previous_summary), self.budget.count(&lines.join("\n"))],
+            &[1, 3],
+        );
+        // Whatever doesn't fit is left for the next run rather than cut
+        let used = self.budget.fit_leading(&lines, grants[1]);
+        let transcript = self.budget.truncate(&lines[..used].join("\n"), grants[1]);
+
+        let rendered = render(&self.budget.truncate(previous_summary, grants[0]), &transcript)?;
+        let messages = vec![
+            LlmMessage {
+                role: "system".to_string(),
+                content: rendered.system,
+            },
+            LlmMessage {
+                role: "user".to_string(),
+                content: rendered.prompt,
+            },
+        ];
+
+        // The summary is stored for the same user and redacted again before every later call
+        let summary = self.call_llm_restoring(messages, known_names, 0.2, 768).await?;
+        Ok((summary.trim().to_string(), used, rendered.version))
     }
 
-    /// After a conversation, update the agent's understanding of the user
+    /// After a conversation, update the agent's understanding of the user.
+    /// Returns the new profile and the version of the prompt template that produced it.
     pub async fn update_user_profile(
         &self,
         history: &[ChatMessage],
         current_profile: &AgentProfile,
-    ) -> Result<AgentProfile, String> {
-        let recent_conversation: String = history
-            .iter()
-            .rev()
-            .take(30)
-            .rev()
-            .map(|m| format!("{}: {}", m.role, m.content))
-            .collect::<Vec<_>>()
-            .join("\n");
+        feedback: &[FeedbackEvidence],
+        known_names: &[String],
+    ) -> Result<(AgentProfile, String), String> {
+        let render = |conversation: &str, feedback: &str| {
+            self.prompts.render(
+                "profile_update",
+                minijinja::context! { profile => current_profile, conversation, feedback },
+            )
+        };
 
-        let prompt = format!(
-            r#"Based on the following conversation with a user, update the user profile. Extract and summarize key information.
-
-Current profile:
-- Personality: {}
-- Interests: {}
-- Core Values: {}
-- Communication style: {}
-- Looking for in a partner: {}
-- Deal breakers: {}
-- Additional notes: {}
-
-Recent conversation:
-{}
-
-Respond in EXACTLY this JSON format (update fields with new info, keep existing info that's still valid):
-{{
-    "personality_summary": "...",
-    "interests": "...",
-    "core_values": "...",
-    "communication_style": "...",
-    "looking_for": "...",
-    "deal_breakers": "...",
-    "raw_notes": "..."
-}}
-
-Only output the JSON, nothing else."#,
-            current_profile.personality_summary,
-            current_profile.interests,
-            current_profile.core_values,
-            current_profile.communication_style,
-            current_profile.looking_for,
-            current_profile.deal_breakers,
-            current_profile.raw_notes,
-            recent_conversation,
+        // The current profile is never cut: whatever the model echoes back gets saved
+        let fixed = render("", "")?;
+        let available = self.budget.available(&[&fixed.system, &fixed.prompt], 2048);
+        let lines: Vec<String> = history.iter().map(|m| format!("{}: {}", m.role, m.content)).collect();
+        let feedback = format_feedback(feedback);
+        let grants = self.budget.allocate(
+            available,
+            &[self.budget.items_cost(&lines), self.budget.count(&feedback)],
+            &[5, 1],
         );
+        let (kept, earlier) = self.budget.fit_recent(&lines, grants[0]);
+        let conversation = if earlier.is_empty() {
+            kept.join("\n")
+        } else {
+            format!("{}\n{}", earlier, kept.join("\n"))
+        };
 
+        let rendered = render(&conversation, &self.budget.truncate(&feedback, grants[1]))?;
         let messages = vec![
             LlmMessage {
                 role: "system".to_string(),
-                content: "You are a profile analysis AI. You extract personality traits, interests, values, and preferences from conversations. Always respond with valid JSON only.".to_string(),
+                content: rendered.system,
             },
             LlmMessage {
                 role: "user".to_string(),
-                content: prompt,
+                content: rendered.prompt,
             },
         ];
 
-        let response = self.call_llm(messages, 0.3, 2048).await?;
+        // The profile is the user's own, so it's stored with their details put back; other
+        // agents only ever see it through `visibility::profile_for`, which hides their names
+        let response = self.call_llm_restoring(messages, known_names, 0.3, 2048).await?;
 
         // Try to parse the JSON response
         let cleaned = response
@@ -196,7 +278,7 @@ Only output the JSON, nothing else."#,
         let parsed: serde_json::Value = serde_json::from_str(cleaned)
             .map_err(|e| format!("Failed to parse profile update: {} — raw: {}", e, cleaned))?;
 
-        Ok(AgentProfile {
+        let profile = AgentProfile {
             user_id: current_profile.user_id.clone(),
             personality_summary: parsed["personality_summary"]
                 .as_str()
@@ -227,87 +309,87 @@ Only output the JSON, nothing else."#,
                 .unwrap_or(&current_profile.raw_notes)
                 .to_string(),
             updated_at: chrono::Utc::now().to_rfc3339(),
-        })
+        };
+        Ok((profile, rendered.version))
+    }
+
+    /// Full names the user introduced themselves with in these messages
+    pub fn introduced_names(&self, history: &[ChatMessage]) -> Vec<String> {
+        self.redactor
+            .introduced_names(history.iter().filter(|m| m.role == "user").map(|m| m.content.as_str()))
+    }
+
+    /// The potential-match block of the matching prompt for `profile`, redacted exactly as it
+    /// would be sent. `profile` should already be filtered by the user's visibility settings.
+    pub fn disclosure_preview(&self, profile: &AgentProfile, known_names: &[String]) -> String {
+        let mut messages = vec![LlmMessage {
+            role: "user".to_string(),
+            content: describe_profile(profile),
+        }];
+        self.redactor.redact_messages(&mut messages, known_names);
+        messages.remove(0).content
     }
 
-    /// Agent-to-agent evaluation: one agent evaluates another user for compatibility
+    /// Agent-to-agent evaluation: one agent evaluates another user for compatibility.
+    /// Both profiles must already be filtered for their audience (see `visibility::profile_for`).
+    /// Returns (score, notes, recommends, prompt template version).
     pub async fn evaluate_compatibility(
         &self,
         my_user_profile: &AgentProfile,
         other_user_profile: &AgentProfile,
         existing_notes: Option<&AgentPeerNote>,
-    ) -> Result<(f64, String, bool), String> {
+        feedback: &[FeedbackEvidence],
+        known_names: &[String],
+    ) -> Result<(f64, String, bool, String), String> {
+        let render = |mine: &str, theirs: &str, previous: &str, feedback: &str| {
+            self.prompts.render(
+                "compatibility",
+                minijinja::context! { mine, theirs, previous, feedback },
+            )
+        };
+
+        let fixed = render("", "", "", "")?;
+        let available = self.budget.available(&[&fixed.system, &fixed.prompt], 1024);
+        let profile_tokens = |p: &AgentProfile| PROFILE_FIELDS.iter().map(|f| self.budget.count(profile_field(p, f))).sum();
+        let previous_notes = existing_notes.map(|n| n.notes.as_str()).unwrap_or_default();
+        let feedback = format_feedback(feedback);
+        let grants = self.budget.allocate(
+            available,
+            &[
+                profile_tokens(my_user_profile),
+                profile_tokens(other_user_profile),
+                self.budget.count(previous_notes),
+                self.budget.count(&feedback),
+            ],
+            &[3, 3, 2, 2],
+        );
+
         let previous_context = match existing_notes {
             Some(notes) => format!(
                 "\nPrevious evaluation notes: {}\nPrevious compatibility score: {:.0}%\nTimes evaluated: {}",
-                notes.notes, notes.compatibility_score * 100.0, notes.conversation_count
+                self.budget.truncate(&notes.notes, grants[2]), notes.compatibility_score * 100.0, notes.conversation_count
             ),
             None => String::from("\nThis is the first evaluation."),
         };
 
-        let prompt = format!(
-            r#"You are an AI matchmaking agent. Your client has the following profile:
-
-YOUR CLIENT:
-- Personality: {}
-- Interests: {}
-- Core Values: {}
-- Communication style: {}
-- Looking for: {}
-- Deal breakers: {}
-
-POTENTIAL MATCH:
-- Personality: {}
-- Interests: {}
-- Core Values: {}
-- Communication style: {}
-- Looking for: {}
-- Deal breakers: {}
-{}
-
-Evaluate the compatibility between your client and this potential match. Consider:
-1. Shared interests and values
-2. Compatible communication styles
-3. Whether each person matches what the other is looking for
-4. Any deal breakers
-5. Potential for genuine connection
-
-Respond in EXACTLY this JSON format:
-{{
-    "compatibility_score": 0.75,
-    "notes": "Detailed analysis of compatibility...",
-    "recommends_match": true
-}}
-
-Score from 0.0 to 1.0. recommends_match should be true if score >= 0.65.
-Only output JSON, nothing else."#,
-            my_user_profile.personality_summary,
-            my_user_profile.interests,
-            my_user_profile.core_values,
-            my_user_profile.communication_style,
-            my_user_profile.looking_for,
-            my_user_profile.deal_breakers,
-            other_user_profile.personality_summary,
-            other_user_profile.interests,
-            other_user_profile.core_values,
-            other_user_profile.communication_style,
-            other_user_profile.looking_for,
-            other_user_profile.deal_breakers,
-            previous_context,
-        );
-
+        let rendered = render(
+            &describe_profile(&self.fit_profile(my_user_profile, grants[0])),
+            &describe_profile(&self.fit_profile(other_user_profile, grants[1])),
+            &previous_context,
+            &self.budget.truncate(&feedback, grants[3]),
+        )?;
         let messages = vec![
             LlmMessage {
                 role: "system".to_string(),
-                content: "You are a compatibility evaluation AI for a dating app. Be thorough but fair. Respond with JSON only.".to_string(),
+                content: rendered.system,
             },
             LlmMessage {
                 role: "user".to_string(),
-                content: prompt,
+                content: rendered.prompt,
             },
         ];
 
-        let response = self.call_llm(messages, 0.4, 1024).await?;
+        let response = self.call_llm(messages, known_names, 0.4, 1024).await?;
 
         let cleaned = response
             .trim()
@@ -331,6 +413,219 @@ Only output JSON, nothing else."#,
             .as_bool()
             .unwrap_or(score >= 0.65);
 
-        Ok((score, notes, recommends))
+        Ok((score, notes, recommends, rendered.version))
+    }
+
+    /// Write conversation openers for a newly confirmed match, from one side's point of view
+    /// Both profiles should be what each user shares with other agents, and the notes cleaned
+    /// of anything that came from agent-only fields or private feedback.
+    /// Returns the openers and the version of the prompt template that produced them.
+    pub async fn generate_icebreakers(
+        &self,
+        client_profile: &AgentProfile,
+        match_profile: &AgentProfile,
+        my_notes_about_them: &str,
+        their_notes_about_me: &str,
+        known_names: &[String],
+    ) -> Result<(Vec<String>, String), String> {
+        let render = |client: &str, other: &str, mine: &str, theirs: &str| {
+            self.prompts.render("icebreakers", minijinja::context! { client, other, mine, theirs })
+        };
+
+        let fixed = render("", "", "", "")?;
+        let available = self.budget.available(&[&fixed.system, &fixed.prompt], 512);
+        let (client, other) = (describe_profile(client_profile), describe_profile(match_profile));
+        let parts = self.budget.fit_fields(&[&client, &other, my_notes_about_them, their_notes_about_me], available);
+
+        let rendered = render(&parts[0], &parts[1], &parts[2], &parts[3])?;
+        let messages = vec![
+            LlmMessage {
+                role: "system".to_string(),
+                content: rendered.system,
+            },
+            LlmMessage {
+                role: "user".to_string(),
+                content: rendered.prompt,
+            },
+        ];
+
+        let response = self.call_llm(messages, known_names, 0.8, 512).await?;
+
+        let cleaned = response
+            .trim()
+            .trim_start_matches("```json")
+            .trim_start_matches("```")
+            .trim_end_matches("```")
+            .trim();
+
+        let openers: Vec<String> = serde_json::from_str(cleaned)
+            .map_err(|e| format!("Failed to parse icebreakers: {} — raw: {}", e, cleaned))?;
+
+        let openers = openers
+            .into_iter()
+            .map(|o| o.trim().to_string())
+            .filter(|o| !o.is_empty())
+            .collect();
+        Ok((openers, rendered.version))
+    }
+
+    /// Draft replies for the user in one of their DM threads. Only the user's own profile is
+    /// used; `thread` holds (sent_by_me, content) pairs, oldest first.
+    pub async fn suggest_replies(
+        &self,
+        my_profile: &AgentProfile,
+        their_name: &str,
+        thread: &[(bool, String)],
+        hint: Option<&str>,
+        known_names: &[String],
+    ) -> Result<Vec<String>, String> {
+        let render = |conversation: &str| {
+            self.prompts.render(
+                "reply_suggestions",
+                minijinja::context! { their_name, profile => my_profile, conversation, hint },
+            )
+        };
+
+        let conversation = if thread.is_empty() {
+            "(No messages yet — this would be the first one.)".to_string()
+        } else {
+            let fixed = render("")?;
+            let lines: Vec<String> = thread
+                .iter()
+                .map(|(mine, content)| format!("{}: {}", if *mine { "Me" } else { their_name }, content))
+                .collect();
+            let (kept, earlier) = self.budget.fit_recent(&lines, self.budget.available(&[&fixed.system, &fixed.prompt], 512));
+            if earlier.is_empty() {
+                kept.join("\n")
+            } else {
+                format!("{}\n{}", earlier, kept.join("\n"))
+            }
+        };
+
+        let rendered = render(&conversation)?;
+        let messages = vec![
+            LlmMessage {
+                role: "system".to_string(),
+                content: rendered.system,
+            },
+            LlmMessage {
+                role: "user".to_string(),
+                content: rendered.prompt,
+            },
+        ];
+
+        let response = self.call_llm_restoring(messages, known_names, 0.9, 512).await?;
+
+        let cleaned = response
+            .trim()
+            .trim_start_matches("```json")
+            .trim_start_matches("```")
+            .trim_end_matches("```")
+            .trim();
+
+        let suggestions: Vec<String> = serde_json::from_str(cleaned)
+            .map_err(|e| format!("Failed to parse reply suggestions: {} — raw: {}", e, cleaned))?;
+
+        Ok(suggestions
+            .into_iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect())
+    }
+
+    /// Classify a user-written message for the moderation pipeline.
+    /// Returns (verdict, category, reason) where verdict is "allow", "flag" or "block".
+    pub async fn classify_message(&self, context: &str, text: &str, known_names: &[String]) -> Result<(String, String, String), String> {
+        let render = |text: &str| self.prompts.render("moderation", minijinja::context! { context, text });
+
+        let fixed = render("")?;
+        let available = self.budget.available(&[&fixed.system, &fixed.prompt], 256);
+        let rendered = render(&self.budget.truncate(text, available))?;
+
+        let messages = vec![
+            LlmMessage {
+                role: "system".to_string(),
+                content: rendered.system,
+            },
+            LlmMessage {
+                role: "user".to_string(),
+                content: rendered.prompt,
+            },
+        ];
+
+        let response = self.call_llm(messages, known_names, 0.0, 256).await?;
+
+        let cleaned = response
+            .trim()
+            .trim_start_matches("```json")
+            .trim_start_matches("```")
+            .trim_end_matches("```")
+            .trim();
+
+        let parsed: serde_json::Value = serde_json::from_str(cleaned)
+            .map_err(|e| format!("Failed to parse moderation verdict: {} — raw: {}", e, cleaned))?;
+
+        let verdict = match parsed["verdict"].as_str().unwrap_or("allow") {
+            v @ ("allow" | "flag" | "block") => v.to_string(),
+            other => return Err(format!("Unknown moderation verdict: {}", other)),
+        };
+
+        Ok((
+            verdict,
+            parsed["category"].as_str().unwrap_or("none").to_string(),
+            parsed["reason"].as_str().unwrap_or_default().to_string(),
+        ))
     }
 }
+
+/// Long-term memory section of the chat system prompt; empty when there's nothing yet
+fn format_memory(memory: &ConversationMemory) -> String {
+    let mut out = String::new();
+    if !memory.summary.is_empty() {
+        out.push_str(&format!("\n\nWhat you remember from earlier conversations:\n{}", memory.summary));
+    }
+    if !memory.recalled.is_empty() {
+        let recalled = memory
+            .recalled
+            .iter()
+            .map(|m| format!("- [{}] {}: {}", m.created_at.as_deref().unwrap_or("earlier"), m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        out.push_str(&format!("\n\nEarlier messages that may be relevant now:\n{}", recalled));
+    }
+    out
+}
+
+/// Profile fields as they appear in matching prompts; withheld or unknown fields say so
+fn describe_profile(profile: &AgentProfile) -> String {
+    let shown = |value: &str| if value.is_empty() { "Not shared".to_string() } else { value.to_string() };
+    format!(
+        "- Personality: {}\n- Interests: {}\n- Core Values: {}\n- Communication style: {}\n- Looking for: {}\n- Deal breakers: {}\n- Additional notes: {}",
+        shown(&profile.personality_summary),
+        shown(&profile.interests),
+        shown(&profile.core_values),
+        shown(&profile.communication_style),
+        shown(&profile.looking_for),
+        shown(&profile.deal_breakers),
+        shown(&profile.raw_notes),
+    )
+}
+
+fn format_feedback(feedback: &[FeedbackEvidence]) -> String {
+    if feedback.is_empty() {
+        return "No feedback yet.".to_string();
+    }
+    feedback
+        .iter()
+        .map(|f| {
+            format!(
+                "- Rated {}/5{}: {}\n  (Agent's notes beforehand: {})",
+                f.rating,
+                if f.met_in_person { " after meeting in person" } else { "" },
+                if f.comment.is_empty() { "no comment" } else { &f.comment },
+                if f.agent_notes.is_empty() { "none" } else { &f.agent_notes },
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,353 @@
+use actix_multipart::Multipart;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+use img_parts::jpeg::{markers, Jpeg};
+use img_parts::png::Png;
+use img_parts::webp::{WebP, CHUNK_XMP};
+use img_parts::{Bytes, ImageEXIF};
+use uuid::Uuid;
+
+use crate::auth::extract_user_id;
+use crate::broker::Broker;
+use crate::db::Database;
+use crate::models::*;
+use crate::moderation::{record_flag, screen, ModerationContext, Moderator, Verdict};
+use crate::realtime::{match_participants, publish_to_match};
+use crate::routes::get_direct_message_db;
+use crate::storage::BlobStore;
+
+pub const ATTACHMENT_COLUMNS: &str = "id, file_name, content_type, size_bytes, created_at";
+
+const MAX_FILES_PER_MESSAGE: usize = 4;
+const MAX_CAPTION_BYTES: usize = 4000;
+
+pub fn attachment_from_row(row: &rusqlite::Row) -> rusqlite::Result<Attachment> {
+    let id: String = row.get(0)?;
+    Ok(Attachment {
+        url: format!("/v1/attachments/{}", id),
+        id,
+        file_name: row.get(1)?,
+        content_type: row.get(2)?,
+        size_bytes: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+fn attachment_max_bytes() -> usize {
+    std::env::var("ATTACHMENT_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10 * 1024 * 1024)
+}
+
+/// Identify a file by its magic bytes. Only these types are accepted, whatever the client claims.
+fn sniff_content_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if bytes.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+/// Drop EXIF (camera, GPS, timestamps) and other embedded text metadata from images
+fn strip_metadata(content_type: &str, bytes: Vec<u8>) -> Result<Vec<u8>, img_parts::Error> {
+    match content_type {
+        "image/jpeg" => {
+            let mut jpeg = Jpeg::from_bytes(Bytes::from(bytes))?;
+            // APP1 carries both EXIF and XMP
+            jpeg.remove_segments_by_marker(markers::APP1);
+            Ok(jpeg.encoder().bytes().to_vec())
+        }
+        "image/png" => {
+            let mut png = Png::from_bytes(Bytes::from(bytes))?;
+            for kind in [*b"eXIf", *b"tEXt", *b"iTXt", *b"zTXt"] {
+                png.remove_chunks_by_type(kind);
+            }
+            Ok(png.encoder().bytes().to_vec())
+        }
+        "image/webp" => {
+            let mut webp = WebP::from_bytes(Bytes::from(bytes))?;
+            webp.remove_chunks_by_id(CHUNK_XMP);
+            webp.set_exif(None);
+            Ok(webp.encoder().bytes().to_vec())
+        }
+        _ => Ok(bytes),
+    }
+}
+
+/// Keep only the final path component and cap the length
+fn sanitize_file_name(name: Option<&str>) -> String {
+    let name = name
+        .and_then(|n| n.rsplit(['/', '\\']).next())
+        .map(|n| n.trim().chars().filter(|c| !c.is_control()).take(200).collect::<String>())
+        .unwrap_or_default();
+    if name.is_empty() || name == "." || name == ".." {
+        "attachment".to_string()
+    } else {
+        name
+    }
+}
+
+struct Upload {
+    file_name: String,
+    content_type: &'static str,
+    bytes: Vec<u8>,
+}
+
+pub async fn upload_attachments(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    blobs: web::Data<dyn BlobStore>,
+    moderator: web::Data<dyn Moderator>,
+    path: web::Path<i64>,
+    mut payload: Multipart,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let match_id = path.into_inner();
+    {
+        let conn = db.conn.lock().unwrap();
+        if match_participants(&conn, match_id, &claims.sub).is_none() {
+            return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}));
+        }
+    }
+
+    let max_bytes = attachment_max_bytes();
+    let mut content = String::new();
+    let mut uploads: Vec<Upload> = Vec::new();
+
+    loop {
+        let mut field = match payload.try_next().await {
+            Ok(Some(f)) => f,
+            Ok(None) => break,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Invalid upload: {}", e)}));
+            }
+        };
+
+        let name = field.name().unwrap_or_default().to_string();
+        let limit = if name == "file" { max_bytes } else { MAX_CAPTION_BYTES };
+        let mut bytes = Vec::new();
+        loop {
+            match field.try_next().await {
+                Ok(Some(chunk)) => {
+                    if bytes.len() + chunk.len() > limit {
+                        return HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                            "error": format!("'{}' exceeds the {} byte limit", name, limit)
+                        }));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Invalid upload: {}", e)}));
+                }
+            }
+        }
+
+        match name.as_str() {
+            "content" => content = String::from_utf8_lossy(&bytes).trim().to_string(),
+            "file" => {
+                if uploads.len() >= MAX_FILES_PER_MESSAGE {
+                    return HttpResponse::BadRequest().json(serde_json::json!({
+                        "error": format!("At most {} files per message", MAX_FILES_PER_MESSAGE)
+                    }));
+                }
+                let content_type = match sniff_content_type(&bytes) {
+                    Some(t) => t,
+                    None => {
+                        return HttpResponse::UnsupportedMediaType().json(serde_json::json!({
+                            "error": "Only JPEG, PNG, GIF, WebP and PDF files are allowed"
+                        }));
+                    }
+                };
+                // A declared type that disagrees with the contents is a disguised file
+                if let Some(declared) = field.content_type()
+                    && declared.essence_str() != "application/octet-stream"
+                    && declared.essence_str() != content_type
+                {
+                    return HttpResponse::UnsupportedMediaType().json(serde_json::json!({
+                        "error": format!("File content is {}, not {}", content_type, declared.essence_str())
+                    }));
+                }
+                let bytes = match strip_metadata(content_type, bytes) {
+                    Ok(b) => b,
+                    Err(_) => {
+                        return HttpResponse::BadRequest().json(serde_json::json!({"error": "Could not read image"}));
+                    }
+                };
+                let file_name = sanitize_file_name(field.content_disposition().and_then(|cd| cd.get_filename()));
+                uploads.push(Upload { file_name, content_type, bytes });
+            }
+            _ => {}
+        }
+    }
+
+    if uploads.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "No file uploaded"}));
+    }
+
+    let verdict = if content.is_empty() {
+        Verdict::allow()
+    } else {
+        match screen(moderator.get_ref(), &db, &claims.sub, ModerationContext::DirectMessage, Some(match_id), &content).await {
+            Ok(v) => v,
+            Err(e) => return e,
+        }
+    };
+
+    // Store the blobs before touching the database; a failed insert cleans them up again
+    let keys: Vec<String> = uploads.iter().map(|_| Uuid::new_v4().simple().to_string()).collect();
+    for (key, upload) in keys.iter().zip(&uploads) {
+        let store = blobs.clone();
+        let key_owned = key.clone();
+        let bytes = upload.bytes.clone();
+        let stored = web::block(move || store.put(&key_owned, &bytes)).await;
+        if !matches!(stored, Ok(Ok(()))) {
+            log::error!("Failed to store attachment {}", key);
+            remove_blobs(blobs.get_ref(), &keys);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to store attachment"}));
+        }
+    }
+
+    let conn = db.conn.lock().unwrap();
+    let participants = match match_participants(&conn, match_id, &claims.sub) {
+        Some(p) => p,
+        None => {
+            remove_blobs(blobs.get_ref(), &keys);
+            return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}));
+        }
+    };
+
+    let inserted = (|| -> rusqlite::Result<i64> {
+        conn.execute_batch("BEGIN")?;
+        let result = (|| {
+            conn.execute(
+                "INSERT INTO direct_messages (match_id, sender_id, content) VALUES (?1, ?2, ?3)",
+                rusqlite::params![match_id, &claims.sub, &content],
+            )?;
+            let message_id = conn.last_insert_rowid();
+            record_flag(&conn, &claims.sub, ModerationContext::DirectMessage, Some(match_id), Some(message_id), &content, &verdict);
+            for (key, upload) in keys.iter().zip(&uploads) {
+                conn.execute(
+                    "INSERT INTO dm_attachments (id, message_id, uploader_id, file_name, content_type, size_bytes, storage_key) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    rusqlite::params![
+                        Uuid::new_v4().to_string(),
+                        message_id,
+                        &claims.sub,
+                        &upload.file_name,
+                        upload.content_type,
+                        upload.bytes.len() as i64,
+                        key,
+                    ],
+                )?;
+            }
+            Ok(message_id)
+        })();
+        match result {
+            Ok(id) => conn.execute_batch("COMMIT").map(|_| id),
+            Err(e) => {
+                let _ = conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    })();
+
+    let msg = match inserted.ok().and_then(|id| get_direct_message_db(&conn, match_id, id)) {
+        Some(m) => m,
+        None => {
+            remove_blobs(blobs.get_ref(), &keys);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to send message"}));
+        }
+    };
+
+    publish_to_match(broker.get_ref(), &participants, RealtimeEvent::Message { message: msg.clone() });
+
+    HttpResponse::Ok().json(msg)
+}
+
+pub async fn get_attachment(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    blobs: web::Data<dyn BlobStore>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let attachment_id = path.into_inner();
+    let found = {
+        let conn = db.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT dm.match_id, a.file_name, a.content_type, a.storage_key FROM dm_attachments a
+             JOIN direct_messages dm ON dm.id = a.message_id
+             WHERE a.id = ?1 AND dm.deleted_at IS NULL",
+            rusqlite::params![&attachment_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?)),
+        )
+        .ok()
+        .filter(|(match_id, _, _, _)| match_participants(&conn, *match_id, &claims.sub).is_some())
+    };
+
+    // Non-participants get the same answer as for a missing attachment
+    let (_, file_name, content_type, storage_key) = match found {
+        Some(a) => a,
+        None => return HttpResponse::NotFound().json(serde_json::json!({"error": "Attachment not found"})),
+    };
+
+    let store = blobs.clone();
+    let bytes = match web::block(move || store.get(&storage_key)).await {
+        Ok(Ok(b)) => b,
+        _ => {
+            log::error!("Attachment {} is missing from the blob store", attachment_id);
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "Attachment not found"}));
+        }
+    };
+
+    let disposition = if content_type.starts_with("image/") { "inline" } else { "attachment" };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Disposition", format!("{}; filename=\"{}\"", disposition, file_name.replace('"', ""))))
+        .insert_header(("X-Content-Type-Options", "nosniff"))
+        .insert_header(("Cache-Control", "private, max-age=3600"))
+        .body(bytes)
+}
+
+pub fn remove_blobs(blobs: &dyn BlobStore, keys: &[String]) {
+    for key in keys {
+        if let Err(e) = blobs.delete(key) {
+            log::warn!("Failed to delete blob {}: {}", key, e);
+        }
+    }
+}
+
+/// Drop the attachment rows of the given messages and return their storage keys; call
+/// `remove_blobs` with them once the surrounding change has been committed.
+pub fn detach_message_attachments(conn: &rusqlite::Connection, where_clause: &str, param: &dyn rusqlite::ToSql) -> rusqlite::Result<Vec<String>> {
+    let keys: Vec<String> = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT storage_key FROM dm_attachments WHERE message_id IN (SELECT id FROM direct_messages WHERE {})",
+            where_clause
+        ))?;
+        stmt.query_map([param], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?
+    };
+    conn.execute(
+        &format!("DELETE FROM dm_attachments WHERE message_id IN (SELECT id FROM direct_messages WHERE {})", where_clause),
+        [param],
+    )?;
+    Ok(keys)
+}
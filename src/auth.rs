@@ -1,15 +1,50 @@
 use actix_web::{web, HttpRequest, HttpResponse};
-use bcrypt::{hash, verify, DEFAULT_COST};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use uuid::Uuid;
 
+use crate::attachments::{detach_message_attachments, remove_blobs};
 use crate::db::Database;
 use crate::models::*;
+use crate::password::{hash_password_async, verify_password_async};
+use crate::storage::BlobStore;
 
 pub fn jwt_secret() -> String {
     std::env::var("JWT_SECRET").unwrap_or_else(|_| "jupiter-secret-key-change-me".to_string())
 }
 
+/// Days an account stays recoverable after `DELETE /v1/auth/account` before it is purged
+pub fn account_deletion_grace_days() -> i64 {
+    std::env::var("ACCOUNT_DELETION_GRACE_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30)
+}
+
+/// Usernames are 3–30 chars of ASCII letters, digits, `_` or `.`, starting with a letter or digit
+pub fn validate_username(username: &str) -> Result<(), &'static str> {
+    if username.len() < 3 || username.len() > 30 {
+        return Err("Username must be 3-30 chars");
+    }
+    if !username.chars().next().is_some_and(|c| c.is_ascii_alphanumeric()) {
+        return Err("Username must start with a letter or digit");
+    }
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return Err("Username may only contain letters, digits, '_' and '.'");
+    }
+    Ok(())
+}
+
+/// A former username stays reserved for its owner so old @-references keep resolving
+fn username_reserved(conn: &rusqlite::Connection, username: &str, except_user_id: &str) -> bool {
+    conn.query_row(
+        "SELECT COUNT(*) FROM username_history WHERE old_username = ?1 COLLATE NOCASE AND user_id != ?2",
+        rusqlite::params![username, except_user_id],
+        |row| row.get::<_, i64>(0),
+    )
+    .unwrap_or(0)
+        > 0
+}
+
 pub fn extract_user_id(req: &HttpRequest) -> Result<Claims, HttpResponse> {
     let auth_header = req
         .headers()
@@ -21,6 +56,26 @@ pub fn extract_user_id(req: &HttpRequest) -> Result<Claims, HttpResponse> {
         .strip_prefix("Bearer ")
         .ok_or_else(|| HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid Authorization format"})))?;
 
+    authenticate_token(req, token)
+}
+
+/// For WebSocket and EventSource clients, which can't set headers: falls back to a `token` query parameter
+pub fn extract_user_id_or_query(req: &HttpRequest) -> Result<Claims, HttpResponse> {
+    if req.headers().contains_key("Authorization") {
+        return extract_user_id(req);
+    }
+
+    let query = web::Query::<TokenQuery>::from_query(req.query_string())
+        .map_err(|_| HttpResponse::BadRequest().json(serde_json::json!({"error": "Invalid query string"})))?;
+    let token = query
+        .token
+        .as_deref()
+        .ok_or_else(|| HttpResponse::Unauthorized().json(serde_json::json!({"error": "Missing token"})))?;
+
+    authenticate_token(req, token)
+}
+
+fn authenticate_token(req: &HttpRequest, token: &str) -> Result<Claims, HttpResponse> {
     let secret = jwt_secret();
     let token_data = decode::<Claims>(
         token,
@@ -32,9 +87,66 @@ pub fn extract_user_id(req: &HttpRequest) -> Result<Claims, HttpResponse> {
         HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid or expired token"}))
     })?;
 
+    // Tokens outlive account state changes, so check the account is still usable
+    if let Some(db) = req.app_data::<web::Data<Database>>() {
+        let conn = db.conn.lock().unwrap();
+        let status = conn.query_row(
+            "SELECT suspended_at IS NOT NULL, deleted_at IS NOT NULL FROM users WHERE id = ?1",
+            rusqlite::params![&token_data.claims.sub],
+            |row| Ok((row.get::<_, bool>(0)?, row.get::<_, bool>(1)?)),
+        );
+        match status {
+            Ok((true, _)) => {
+                return Err(HttpResponse::Forbidden().json(serde_json::json!({"error": "Account suspended"})));
+            }
+            Ok((_, true)) | Err(_) => {
+                return Err(HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid or expired token"})));
+            }
+            Ok(_) => {}
+        }
+    }
+
     Ok(token_data.claims)
 }
 
+/// Like `extract_user_id`, but only lets through users whose current role is `admin`
+pub fn require_admin(req: &HttpRequest) -> Result<Claims, HttpResponse> {
+    let claims = extract_user_id(req)?;
+    let forbidden = || HttpResponse::Forbidden().json(serde_json::json!({"error": "Admin access required"}));
+
+    if claims.role != "admin" {
+        return Err(forbidden());
+    }
+
+    // The claim may predate a demotion, so confirm against the database
+    let db = req.app_data::<web::Data<Database>>().ok_or_else(forbidden)?;
+    let conn = db.conn.lock().unwrap();
+    let role: String = conn
+        .query_row(
+            "SELECT role FROM users WHERE id = ?1",
+            rusqlite::params![&claims.sub],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+
+    if role != "admin" {
+        return Err(forbidden());
+    }
+    Ok(claims)
+}
+
+pub fn issue_token(user_id: &str, username: &str, role: &str) -> String {
+    let secret = jwt_secret();
+    let claims = Claims {
+        sub: user_id.to_string(),
+        username: username.to_string(),
+        role: role.to_string(),
+        exp: (chrono::Utc::now() + chrono::Duration::days(30)).timestamp() as usize,
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
+        .unwrap_or_default()
+}
+
 pub async fn register(
     db: web::Data<Database>,
     body: web::Json<RegisterRequest>,
@@ -43,13 +155,14 @@ pub async fn register(
     let email = body.email.trim().to_lowercase();
     let password = body.password.clone();
 
-    if username.len() < 3 || password.len() < 6 {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Username must be 3+ chars, password 6+ chars"
-        }));
+    if let Err(msg) = validate_username(&username) {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": msg}));
+    }
+    if password.len() < 6 {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "Password must be 6+ chars"}));
     }
 
-    let password_hash = match hash(&password, DEFAULT_COST) {
+    let password_hash = match hash_password_async(password).await {
         Ok(h) => h,
         Err(_) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to hash password"})),
     };
@@ -62,14 +175,14 @@ pub async fn register(
     // Check if user exists
     let exists: bool = conn
         .query_row(
-            "SELECT COUNT(*) FROM users WHERE username = ?1 OR email = ?2",
+            "SELECT COUNT(*) FROM users WHERE username = ?1 COLLATE NOCASE OR lower(email) = ?2",
             rusqlite::params![&username, &email],
             |row| row.get::<_, i64>(0),
         )
         .unwrap_or(0)
         > 0;
 
-    if exists {
+    if exists || username_reserved(&conn, &username, "") {
         return HttpResponse::Conflict().json(serde_json::json!({"error": "Username or email already exists"}));
     }
 
@@ -88,14 +201,7 @@ pub async fn register(
     );
 
     // Generate JWT
-    let secret = jwt_secret();
-    let claims = Claims {
-        sub: user_id.clone(),
-        username: username.clone(),
-        exp: (chrono::Utc::now() + chrono::Duration::days(30)).timestamp() as usize,
-    };
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
-        .unwrap_or_default();
+    let token = issue_token(&user_id, &username, "user");
 
     HttpResponse::Ok().json(AuthResponse {
         token,
@@ -114,53 +220,80 @@ pub async fn login(
     db: web::Data<Database>,
     body: web::Json<LoginRequest>,
 ) -> HttpResponse {
-    let conn = db.conn.lock().unwrap();
+    let result = {
+        let conn = db.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, username, email, password_hash, display_name, bio, created_at, role, suspended_at IS NOT NULL FROM users WHERE (username = ?1 COLLATE NOCASE OR lower(email) = lower(?1)) AND deleted_at IS NULL",
+            rusqlite::params![body.username.trim()],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, String>(7)?,
+                    row.get::<_, bool>(8)?,
+                ))
+            },
+        )
+    };
 
-    let result = conn.query_row(
-        "SELECT id, username, email, password_hash, display_name, bio, created_at FROM users WHERE username = ?1",
-        rusqlite::params![&body.username],
-        |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, String>(3)?,
-                row.get::<_, String>(4)?,
-                row.get::<_, String>(5)?,
-                row.get::<_, String>(6)?,
-            ))
-        },
-    );
+    let (id, username, email, password_hash, display_name, bio, created_at, role, suspended) = match result {
+        Ok(row) => row,
+        Err(_) => return HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid credentials"})),
+    };
 
-    match result {
-        Ok((id, username, email, password_hash, display_name, bio, created_at)) => {
-            if !verify(&body.password, &password_hash).unwrap_or(false) {
-                return HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid credentials"}));
-            }
+    // Hashing happens without the database lock, so a login doesn't stall every other request
+    let check = verify_password_async(body.password.clone(), password_hash).await;
+    if !check.valid {
+        return HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid credentials"}));
+    }
+    if suspended {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": "Account suspended"}));
+    }
 
-            let secret = jwt_secret();
-            let claims = Claims {
-                sub: id.clone(),
-                username: username.clone(),
-                exp: (chrono::Utc::now() + chrono::Duration::days(30)).timestamp() as usize,
-            };
-            let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
-                .unwrap_or_default();
-
-            HttpResponse::Ok().json(AuthResponse {
-                token,
-                user: UserPublic {
-                    id,
-                    username,
-                    email,
-                    display_name,
-                    bio,
-                    created_at,
-                },
-            })
+    // Upgrade legacy bcrypt hashes (and outdated argon2 parameters) now that we have the plaintext
+    let new_hash = if check.needs_rehash {
+        hash_password_async(body.password.clone())
+            .await
+            .map_err(|e| log::warn!("Password rehash failed for {}: {}", id, e))
+            .ok()
+    } else {
+        None
+    };
+
+    {
+        let conn = db.conn.lock().unwrap();
+        if let Some(new_hash) = new_hash {
+            let _ = conn.execute(
+                "UPDATE users SET password_hash = ?1 WHERE id = ?2",
+                rusqlite::params![&new_hash, &id],
+            );
         }
-        Err(_) => HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid credentials"})),
+
+        // Logging back in during the grace period cancels a pending deletion
+        let _ = conn.execute(
+            "UPDATE users SET deletion_requested_at = NULL WHERE id = ?1 AND deletion_requested_at IS NOT NULL",
+            rusqlite::params![&id],
+        );
     }
+
+    let token = issue_token(&id, &username, &role);
+
+    HttpResponse::Ok().json(AuthResponse {
+        token,
+        user: UserPublic {
+            id,
+            username,
+            email,
+            display_name,
+            bio,
+            created_at,
+        },
+    })
 }
 
 pub async fn get_profile(
@@ -221,3 +354,353 @@ pub async fn update_profile(
 
     HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
 }
+
+pub async fn change_username(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    body: web::Json<ChangeUsernameRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let new_username = body.username.trim().to_string();
+    if let Err(msg) = validate_username(&new_username) {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": msg}));
+    }
+
+    let conn = db.conn.lock().unwrap();
+
+    let current = conn.query_row(
+        "SELECT username, email, display_name, bio, created_at FROM users WHERE id = ?1 AND deleted_at IS NULL",
+        rusqlite::params![&claims.sub],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        },
+    );
+    let (old_username, email, display_name, bio, created_at) = match current {
+        Ok(u) => u,
+        Err(_) => return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"})),
+    };
+
+    if old_username != new_username {
+        let taken: bool = conn
+            .query_row(
+                "SELECT COUNT(*) FROM users WHERE username = ?1 COLLATE NOCASE AND id != ?2",
+                rusqlite::params![&new_username, &claims.sub],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap_or(0)
+            > 0;
+
+        if taken || username_reserved(&conn, &new_username, &claims.sub) {
+            return HttpResponse::Conflict().json(serde_json::json!({"error": "Username already taken"}));
+        }
+
+        if let Err(e) = conn.execute(
+            "UPDATE users SET username = ?1, updated_at = datetime('now') WHERE id = ?2",
+            rusqlite::params![&new_username, &claims.sub],
+        ) {
+            log::error!("Username change error: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to change username"}));
+        }
+
+        // Only a case change keeps the same handle, so there's nothing to remember
+        if !old_username.eq_ignore_ascii_case(&new_username) {
+            let _ = conn.execute(
+                "INSERT INTO username_history (user_id, old_username) VALUES (?1, ?2)",
+                rusqlite::params![&claims.sub, &old_username],
+            );
+        }
+    }
+
+    // Tokens carry the username, so hand out a fresh one
+    let token = issue_token(&claims.sub, &new_username, &claims.role);
+
+    HttpResponse::Ok().json(AuthResponse {
+        token,
+        user: UserPublic {
+            id: claims.sub,
+            username: new_username,
+            email,
+            display_name,
+            bio,
+            created_at,
+        },
+    })
+}
+
+/// Resolve an @-reference, following the rename history when the handle is no longer current
+pub async fn resolve_username(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(e) = extract_user_id(&req) {
+        return e;
+    }
+
+    let username = path.into_inner();
+    let handle = username.trim_start_matches('@');
+    let conn = db.conn.lock().unwrap();
+
+    let current = conn.query_row(
+        "SELECT id, username, display_name FROM users WHERE username = ?1 COLLATE NOCASE AND deleted_at IS NULL",
+        rusqlite::params![handle],
+        |row| {
+            Ok(UserHandle {
+                id: row.get(0)?,
+                username: row.get(1)?,
+                display_name: row.get(2)?,
+                renamed_from: None,
+            })
+        },
+    );
+    if let Ok(user) = current {
+        return HttpResponse::Ok().json(user);
+    }
+
+    let historical = conn.query_row(
+        "SELECT u.id, u.username, u.display_name, h.old_username FROM username_history h
+         JOIN users u ON u.id = h.user_id
+         WHERE h.old_username = ?1 COLLATE NOCASE AND u.deleted_at IS NULL
+         ORDER BY h.changed_at DESC LIMIT 1",
+        rusqlite::params![handle],
+        |row| {
+            Ok(UserHandle {
+                id: row.get(0)?,
+                username: row.get(1)?,
+                display_name: row.get(2)?,
+                renamed_from: Some(row.get(3)?),
+            })
+        },
+    );
+
+    match historical {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(_) => HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"})),
+    }
+}
+
+/// The password hash of a live account. Taken under the lock and returned, so the caller can
+/// verify against it without holding the database.
+fn stored_password_hash(db: &Database, user_id: &str) -> Option<String> {
+    let conn = db.conn.lock().unwrap();
+    conn.query_row(
+        "SELECT password_hash FROM users WHERE id = ?1 AND deleted_at IS NULL",
+        rusqlite::params![user_id],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+pub async fn change_password(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    body: web::Json<ChangePasswordRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    if body.new_password.len() < 6 {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "Password must be 6+ chars"}));
+    }
+
+    let password_hash = match stored_password_hash(&db, &claims.sub) {
+        Some(h) => h,
+        None => return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"})),
+    };
+
+    if !verify_password_async(body.current_password.clone(), password_hash).await.valid {
+        return HttpResponse::Unauthorized().json(serde_json::json!({"error": "Current password is incorrect"}));
+    }
+
+    let new_hash = match hash_password_async(body.new_password.clone()).await {
+        Ok(h) => h,
+        Err(_) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to hash password"})),
+    };
+
+    let conn = db.conn.lock().unwrap();
+    if let Err(e) = conn.execute(
+        "UPDATE users SET password_hash = ?1, updated_at = datetime('now') WHERE id = ?2",
+        rusqlite::params![&new_hash, &claims.sub],
+    ) {
+        log::error!("Password change error: {}", e);
+        return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to update password"}));
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+}
+
+pub async fn delete_account(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    blobs: web::Data<dyn BlobStore>,
+    body: web::Json<DeleteAccountRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let password_hash = match stored_password_hash(&db, &claims.sub) {
+        Some(h) => h,
+        None => return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"})),
+    };
+
+    if !verify_password_async(body.password.clone(), password_hash).await.valid {
+        return HttpResponse::Unauthorized().json(serde_json::json!({"error": "Invalid credentials"}));
+    }
+
+    let conn = db.conn.lock().unwrap();
+    if let Err(e) = conn.execute(
+        "UPDATE users SET deletion_requested_at = COALESCE(deletion_requested_at, datetime('now')) WHERE id = ?1",
+        rusqlite::params![&claims.sub],
+    ) {
+        log::error!("Account deletion error: {}", e);
+        return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to schedule deletion"}));
+    }
+
+    let grace_days = account_deletion_grace_days();
+    if grace_days <= 0 {
+        if let Err(e) = purge_account(&conn, blobs.get_ref(), &claims.sub) {
+            log::error!("Account purge failed for {}: {}", claims.sub, e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to delete account"}));
+        }
+        return HttpResponse::Ok().json(serde_json::json!({"status": "deleted"}));
+    }
+
+    let purge_after: String = conn
+        .query_row(
+            "SELECT datetime(deletion_requested_at, ?2) FROM users WHERE id = ?1",
+            rusqlite::params![&claims.sub, format!("+{} days", grace_days)],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "deletion_scheduled",
+        "purge_after": purge_after,
+    }))
+}
+
+/// Purge every account whose deletion grace period has elapsed. Returns how many were purged.
+pub fn purge_expired_accounts(conn: &rusqlite::Connection, blobs: &dyn BlobStore, grace_days: i64) -> usize {
+    let user_ids: Vec<String> = {
+        let mut stmt = match conn.prepare(
+            "SELECT id FROM users WHERE deleted_at IS NULL AND deletion_requested_at IS NOT NULL AND deletion_requested_at <= datetime('now', ?1)",
+        ) {
+            Ok(s) => s,
+            Err(e) => {
+                log::error!("Account purge query failed: {}", e);
+                return 0;
+            }
+        };
+        stmt.query_map(rusqlite::params![format!("-{} days", grace_days)], |row| row.get(0))
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect()
+    };
+
+    let mut purged = 0;
+    for user_id in &user_ids {
+        match purge_account(conn, blobs, user_id) {
+            Ok(()) => purged += 1,
+            Err(e) => log::error!("Account purge failed for {}: {}", user_id, e),
+        }
+    }
+    purged
+}
+
+/// Erase a user's data. The `users` row is kept as an anonymous tombstone so that
+/// confirmed matches and the other party's side of the DM thread stay intact.
+fn purge_account(conn: &rusqlite::Connection, blobs: &dyn BlobStore, user_id: &str) -> rusqlite::Result<()> {
+    conn.execute_batch("BEGIN")?;
+    let result = (|| {
+        let blob_keys = detach_message_attachments(conn, "sender_id = ?1", &user_id)?;
+        conn.execute("DELETE FROM conversations WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM conversation_summaries WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM data_exports WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM username_history WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM agent_profiles WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM agent_profile_visibility WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM agent_personas WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM known_names WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM onboarding_answers WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM match_preferences WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM agent_profile_locks WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM agent_profile_proposals WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM agent_profile_versions WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute(
+            "DELETE FROM agent_peer_notes WHERE agent_user_id = ?1 OR about_user_id = ?1",
+            rusqlite::params![user_id],
+        )?;
+        conn.execute(
+            "DELETE FROM notifications WHERE user_id = ?1 OR related_user_id = ?1",
+            rusqlite::params![user_id],
+        )?;
+
+        conn.execute("DELETE FROM dm_read_state WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM match_feedback WHERE user_id = ?1", rusqlite::params![user_id])?;
+        conn.execute("DELETE FROM moderation_flags WHERE user_id = ?1", rusqlite::params![user_id])?;
+        // moderation_cases and their events are kept: they are the safety record for reports
+        // filed by or against this user
+        // Openers on either side of their matches were written from their profile
+        conn.execute(
+            "DELETE FROM match_icebreakers WHERE match_id IN (SELECT id FROM matches WHERE user_a_id = ?1 OR user_b_id = ?1)",
+            rusqlite::params![user_id],
+        )?;
+        conn.execute("DELETE FROM dm_reactions WHERE user_id = ?1", rusqlite::params![user_id])?;
+        // Earlier versions of their messages would otherwise survive the redaction below
+        conn.execute(
+            "DELETE FROM dm_edits WHERE message_id IN (SELECT id FROM direct_messages WHERE sender_id = ?1)",
+            rusqlite::params![user_id],
+        )?;
+
+        // Pending proposals go away entirely; confirmed matches keep the thread for the other party
+        conn.execute(
+            "DELETE FROM dm_reactions WHERE message_id IN (SELECT dm.id FROM direct_messages dm JOIN matches m ON m.id = dm.match_id WHERE m.is_matched = 0 AND (m.user_a_id = ?1 OR m.user_b_id = ?1))",
+            rusqlite::params![user_id],
+        )?;
+        conn.execute(
+            "DELETE FROM direct_messages WHERE match_id IN (SELECT id FROM matches WHERE is_matched = 0 AND (user_a_id = ?1 OR user_b_id = ?1))",
+            rusqlite::params![user_id],
+        )?;
+        conn.execute(
+            "DELETE FROM matches WHERE is_matched = 0 AND (user_a_id = ?1 OR user_b_id = ?1)",
+            rusqlite::params![user_id],
+        )?;
+        conn.execute(
+            "UPDATE direct_messages SET content = '[message removed]' WHERE sender_id = ?1",
+            rusqlite::params![user_id],
+        )?;
+
+        conn.execute(
+            "UPDATE users SET username = 'deleted-' || id, email = id || '@deleted.invalid', password_hash = '', display_name = 'Deleted user', bio = '', deleted_at = datetime('now'), updated_at = datetime('now') WHERE id = ?1",
+            rusqlite::params![user_id],
+        )?;
+        Ok(blob_keys)
+    })();
+
+    match result {
+        Ok(blob_keys) => {
+            conn.execute_batch("COMMIT")?;
+            // Files can't be rolled back, so they only go once the purge has committed
+            remove_blobs(blobs, &blob_keys);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::models::RealtimeEvent;
+
+/// Per-user fan-out of real-time events. Handlers only talk to this trait, so the
+/// in-process implementation can later be swapped for Redis, NATS, etc.
+pub trait Broker: Send + Sync {
+    /// Deliver an event to every live subscription of `user_id`. Dropped if the user is offline.
+    fn publish(&self, user_id: &str, event: RealtimeEvent);
+
+    /// Open a new subscription for `user_id`. Dropping the receiver ends it.
+    fn subscribe(&self, user_id: &str) -> broadcast::Receiver<RealtimeEvent>;
+
+    /// Whether `user_id` currently has at least one live subscription
+    fn is_online(&self, user_id: &str) -> bool;
+}
+
+/// Single-process broker backed by one broadcast channel per connected user
+pub struct InProcessBroker {
+    channels: Mutex<HashMap<String, broadcast::Sender<RealtimeEvent>>>,
+}
+
+impl InProcessBroker {
+    pub fn new() -> Self {
+        InProcessBroker {
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Broker for InProcessBroker {
+    fn publish(&self, user_id: &str, event: RealtimeEvent) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(user_id)
+            && sender.send(event).is_err()
+        {
+            // Every receiver is gone — forget the channel
+            channels.remove(user_id);
+        }
+    }
+
+    fn subscribe(&self, user_id: &str) -> broadcast::Receiver<RealtimeEvent> {
+        let mut channels = self.channels.lock().unwrap();
+        channels
+            .entry(user_id.to_string())
+            .or_insert_with(|| broadcast::channel(256).0)
+            .subscribe()
+    }
+
+    fn is_online(&self, user_id: &str) -> bool {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(user_id)
+            .is_some_and(|sender| sender.receiver_count() > 0)
+    }
+}
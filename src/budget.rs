@@ -0,0 +1,335 @@
+use tiktoken_rs::CoreBPE;
+
+/// Tokens each chat message costs on top of its content (role and separators)
+const PER_MESSAGE_TOKENS: usize = 4;
+/// Kept free so placeholder redaction and tokenizer mismatch on non-OpenAI models can't tip
+/// a prompt over the limit
+const SAFETY_MARGIN_TOKENS: usize = 64;
+/// Share of a history allowance reserved for the digest of messages that didn't fit
+const DIGEST_PERCENT: usize = 15;
+/// Tokens kept from each omitted message in a digest
+const DIGEST_LINE_TOKENS: usize = 24;
+
+const TRUNCATION_MARK: &str = " …";
+
+/// Context window and tokenizer for the models we know about, matched by name prefix.
+/// Unknown models fall back to 8k tokens and cl100k, which is close enough for budgeting.
+fn model_defaults(model: &str) -> (usize, &'static str) {
+    let model = model.to_lowercase();
+    let known: &[(&str, usize, &str)] = &[
+        ("gpt-4o", 128_000, "o200k_base"),
+        ("gpt-4.1", 1_000_000, "o200k_base"),
+        ("o1", 128_000, "o200k_base"),
+        ("o3", 200_000, "o200k_base"),
+        ("o4", 200_000, "o200k_base"),
+        ("gpt-4-turbo", 128_000, "cl100k_base"),
+        ("gpt-4", 8_192, "cl100k_base"),
+        ("gpt-3.5", 16_385, "cl100k_base"),
+        ("llama3.1", 131_072, "cl100k_base"),
+        ("llama3.2", 131_072, "cl100k_base"),
+        ("llama3.3", 131_072, "cl100k_base"),
+        ("llama3", 8_192, "cl100k_base"),
+        ("mixtral", 32_768, "cl100k_base"),
+        ("mistral", 32_768, "cl100k_base"),
+        ("qwen", 32_768, "cl100k_base"),
+        ("gemma", 8_192, "cl100k_base"),
+        ("phi3", 4_096, "cl100k_base"),
+    ];
+    known
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))
+        .map(|(_, context, tokenizer)| (*context, *tokenizer))
+        .unwrap_or((8_192, "cl100k_base"))
+}
+
+fn load_tokenizer(name: &str) -> Option<CoreBPE> {
+    match name {
+        "o200k_base" => tiktoken_rs::o200k_base().ok(),
+        "cl100k_base" => tiktoken_rs::cl100k_base().ok(),
+        "p50k_base" => tiktoken_rs::p50k_base().ok(),
+        "r50k_base" => tiktoken_rs::r50k_base().ok(),
+        _ => None,
+    }
+}
+
+/// Counts tokens for the configured model and decides how much of each prompt section fits
+pub struct TokenBudget {
+    bpe: CoreBPE,
+    context_tokens: usize,
+}
+
+impl TokenBudget {
+    /// Defaults come from the model name; `LLM_CONTEXT_TOKENS` and `LLM_TOKENIZER`
+    /// (o200k_base, cl100k_base, p50k_base or r50k_base) override them.
+    pub fn for_model(model: &str) -> Self {
+        let (default_context, default_tokenizer) = model_defaults(model);
+        let context_tokens = std::env::var("LLM_CONTEXT_TOKENS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_context);
+        let tokenizer = std::env::var("LLM_TOKENIZER").unwrap_or_else(|_| default_tokenizer.to_string());
+        let bpe = load_tokenizer(&tokenizer).unwrap_or_else(|| {
+            log::warn!("Unknown LLM_TOKENIZER '{}', using cl100k_base", tokenizer);
+            tiktoken_rs::cl100k_base().expect("bundled cl100k tokenizer")
+        });
+        log::info!("Prompt budget for {}: {} tokens ({})", model, context_tokens, tokenizer);
+        TokenBudget { bpe, context_tokens }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_ordinary(text).len()
+    }
+
+    /// Tokens left for variable content once the fixed parts of the prompt (one string per
+    /// message) and the reply are accounted for
+    pub fn available(&self, fixed_messages: &[&str], reply_tokens: u32) -> usize {
+        let fixed: usize = fixed_messages.iter().map(|m| self.count(m) + PER_MESSAGE_TOKENS).sum();
+        self.context_tokens
+            .saturating_sub(reply_tokens as usize)
+            .saturating_sub(fixed)
+            .saturating_sub(SAFETY_MARGIN_TOKENS)
+    }
+
+    /// Cut `text` to at most `max_tokens`, marking the cut. Always cuts at the same place.
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        let tokens = self.bpe.encode_ordinary(text);
+        if tokens.len() <= max_tokens {
+            return text.to_string();
+        }
+        let mark = self.count(TRUNCATION_MARK);
+        if max_tokens <= mark {
+            return String::new();
+        }
+        // A cut can land inside a multi-byte character; back off until it decodes
+        let mut keep = max_tokens - mark;
+        while keep > 0 {
+            if let Ok(head) = self.bpe.decode(tokens[..keep].to_vec()) {
+                return format!("{}{}", head.trim_end(), TRUNCATION_MARK);
+            }
+            keep -= 1;
+        }
+        String::new()
+    }
+
+    /// Split `available` tokens between sections. Each section gets at most what it needs;
+    /// whatever a small section doesn't use is shared among the others by weight.
+    pub fn allocate(&self, available: usize, needs: &[usize], weights: &[usize]) -> Vec<usize> {
+        let mut granted = vec![0; needs.len()];
+        let mut open: Vec<usize> = (0..needs.len()).collect();
+        let mut remaining = available;
+
+        loop {
+            let total_weight: usize = open.iter().map(|&i| weights[i].max(1)).sum();
+            if open.is_empty() || total_weight == 0 {
+                break;
+            }
+            let satisfied: Vec<usize> = open
+                .iter()
+                .copied()
+                .filter(|&i| needs[i] <= remaining * weights[i].max(1) / total_weight)
+                .collect();
+            if satisfied.is_empty() {
+                for &i in &open {
+                    granted[i] = remaining * weights[i].max(1) / total_weight;
+                }
+                break;
+            }
+            for i in satisfied {
+                granted[i] = needs[i];
+                remaining -= needs[i];
+                open.retain(|&o| o != i);
+            }
+        }
+        granted
+    }
+
+    /// Fit several short fields (profile fields, say) into `max_tokens`, sharing the space
+    /// evenly and truncating only the fields that overflow their share
+    pub fn fit_fields(&self, fields: &[&str], max_tokens: usize) -> Vec<String> {
+        let needs: Vec<usize> = fields.iter().map(|f| self.count(f)).collect();
+        let grants = self.allocate(max_tokens, &needs, &vec![1; fields.len()]);
+        fields.iter().zip(grants).map(|(f, g)| self.truncate(f, g)).collect()
+    }
+
+    /// What `fit_recent` charges for keeping every item
+    pub fn items_cost(&self, items: &[String]) -> usize {
+        items.iter().map(|i| self.count(i) + PER_MESSAGE_TOKENS).sum()
+    }
+
+    /// Keep the newest items that fit in `max_tokens` (the newest is truncated if it alone is
+    /// too long). Older items that don't fit are condensed into a digest of their openings,
+    /// newest first, within a small share of the allowance.
+    /// Returns the kept items, oldest first, and the digest (empty when nothing was dropped).
+    pub fn fit_recent(&self, items: &[String], max_tokens: usize) -> (Vec<String>, String) {
+        if self.items_cost(items) <= max_tokens {
+            return (items.to_vec(), String::new());
+        }
+
+        let digest_budget = max_tokens * DIGEST_PERCENT / 100;
+        let keep_budget = max_tokens - digest_budget;
+
+        let mut kept = Vec::new();
+        let mut used = 0;
+        for item in items.iter().rev() {
+            let cost = self.count(item) + PER_MESSAGE_TOKENS;
+            if used + cost > keep_budget {
+                if kept.is_empty() {
+                    kept.push(self.truncate(item, keep_budget.saturating_sub(PER_MESSAGE_TOKENS)));
+                }
+                break;
+            }
+            used += cost;
+            kept.push(item.clone());
+        }
+        kept.reverse();
+
+        let dropped = &items[..items.len() - kept.len()];
+        let mut lines = Vec::new();
+        let mut digest_used = 0;
+        for item in dropped.iter().rev() {
+            let line = format!("- {}", self.truncate(item, DIGEST_LINE_TOKENS));
+            let cost = self.count(&line) + 1;
+            if digest_used + cost > digest_budget {
+                break;
+            }
+            digest_used += cost;
+            lines.push(line);
+        }
+        lines.reverse();
+
+        let omitted = dropped.len() - lines.len();
+        let mut digest = String::new();
+        if omitted > 0 {
+            digest.push_str(&format!("({} earlier messages omitted)\n", omitted));
+        }
+        digest.push_str(&lines.join("\n"));
+        (kept, digest.trim_end().to_string())
+    }
+
+    /// How many items, oldest first, fit in `max_tokens`. At least one is always taken
+    /// (callers truncate it) so a single huge item can't stall progress.
+    pub fn fit_leading(&self, items: &[String], max_tokens: usize) -> usize {
+        let mut used = 0;
+        let mut n = 0;
+        for item in items {
+            let cost = self.count(item) + 1;
+            if used + cost > max_tokens {
+                break;
+            }
+            used += cost;
+            n += 1;
+        }
+        n.max(1).min(items.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(context_tokens: usize) -> TokenBudget {
+        TokenBudget { bpe: tiktoken_rs::cl100k_base().unwrap(), context_tokens }
+    }
+
+    #[test]
+    fn truncate_leaves_short_text_alone() {
+        let b = budget(8_192);
+        assert_eq!(b.truncate("hello there", 10), "hello there");
+    }
+
+    #[test]
+    fn truncate_fits_and_is_deterministic() {
+        let b = budget(8_192);
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+        let cut = b.truncate(&text, 30);
+        assert!(cut.ends_with(TRUNCATION_MARK));
+        assert!(b.count(&cut) <= 30);
+        assert!(text.starts_with(cut.trim_end_matches(TRUNCATION_MARK)));
+        assert_eq!(cut, b.truncate(&text, 30));
+    }
+
+    #[test]
+    fn truncate_to_nothing_when_only_the_mark_would_fit() {
+        let b = budget(8_192);
+        assert_eq!(b.truncate("a long sentence that will not fit", 1), "");
+    }
+
+    #[test]
+    fn truncate_never_splits_a_character() {
+        let b = budget(8_192);
+        let text = "żółć 🦀 ".repeat(30);
+        for max in 2..20 {
+            let cut = b.truncate(&text, max);
+            assert!(b.count(&cut) <= max);
+        }
+    }
+
+    #[test]
+    fn allocate_grants_needs_when_everything_fits() {
+        let b = budget(8_192);
+        assert_eq!(b.allocate(100, &[10, 20, 30], &[1, 1, 1]), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn allocate_shares_what_small_sections_leave() {
+        let b = budget(8_192);
+        assert_eq!(b.allocate(100, &[10, 200, 200], &[1, 1, 1]), vec![10, 45, 45]);
+        assert_eq!(b.allocate(90, &[1000, 1000], &[1, 2]), vec![30, 60]);
+    }
+
+    #[test]
+    fn allocate_never_exceeds_available() {
+        let b = budget(8_192);
+        let grants = b.allocate(77, &[50, 60, 5, 0], &[2, 5, 1, 1]);
+        assert!(grants.iter().sum::<usize>() <= 77);
+        assert_eq!(grants[2], 5);
+        assert_eq!(grants[3], 0);
+    }
+
+    #[test]
+    fn fit_recent_keeps_everything_that_fits() {
+        let b = budget(8_192);
+        let items = vec!["user: hi".to_string(), "assistant: hello".to_string()];
+        assert_eq!(b.fit_recent(&items, 1_000), (items.clone(), String::new()));
+    }
+
+    #[test]
+    fn fit_recent_keeps_the_newest_and_digests_the_rest() {
+        let b = budget(8_192);
+        let items: Vec<String> = (0..60).map(|i| format!("user: message number {} about hiking and coffee", i)).collect();
+        let (kept, digest) = b.fit_recent(&items, 200);
+
+        assert!(!kept.is_empty() && kept.len() < items.len());
+        assert!(items.ends_with(&kept));
+        assert!(b.items_cost(&kept) <= 200 - 200 * DIGEST_PERCENT / 100);
+        assert!(!digest.is_empty());
+        assert!(b.count(&digest) <= 200 * DIGEST_PERCENT / 100 + 16);
+        assert_eq!((kept.clone(), digest.clone()), b.fit_recent(&items, 200));
+    }
+
+    #[test]
+    fn fit_recent_truncates_a_single_huge_item() {
+        let b = budget(8_192);
+        let items = vec!["word ".repeat(500)];
+        let (kept, _) = b.fit_recent(&items, 50);
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].ends_with(TRUNCATION_MARK));
+    }
+
+    #[test]
+    fn fit_leading_always_takes_one() {
+        let b = budget(8_192);
+        let items: Vec<String> = vec!["word ".repeat(100), "short".to_string()];
+        assert_eq!(b.fit_leading(&items, 5), 1);
+        assert_eq!(b.fit_leading(&items, 10_000), 2);
+        assert_eq!(b.fit_leading(&[], 10), 0);
+    }
+
+    #[test]
+    fn available_subtracts_fixed_parts_and_reply() {
+        let b = budget(1_000);
+        let fixed = "You are a helpful assistant.";
+        assert_eq!(b.available(&[fixed], 100), 1_000 - 100 - (b.count(fixed) + PER_MESSAGE_TOKENS) - SAFETY_MARGIN_TOKENS);
+        assert_eq!(b.available(&[fixed], 5_000), 0);
+    }
+}
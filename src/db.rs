@@ -27,6 +27,13 @@ impl Database {
                 password_hash TEXT NOT NULL,
                 display_name TEXT NOT NULL DEFAULT '',
                 bio TEXT NOT NULL DEFAULT '',
+                role TEXT NOT NULL DEFAULT 'user' CHECK(role IN ('user', 'admin')),
+                suspended_at TEXT,
+                banned_at TEXT,
+                suspension_reason TEXT,
+                deletion_requested_at TEXT,
+                deleted_at TEXT,
+                onboarding_completed_at TEXT,
                 created_at TEXT NOT NULL DEFAULT (datetime('now')),
                 updated_at TEXT NOT NULL DEFAULT (datetime('now'))
             );
@@ -36,10 +43,19 @@ impl Database {
                 user_id TEXT NOT NULL REFERENCES users(id),
                 role TEXT NOT NULL CHECK(role IN ('user', 'assistant', 'system')),
                 content TEXT NOT NULL,
+                prompt_version TEXT,
                 created_at TEXT NOT NULL DEFAULT (datetime('now'))
             );
             CREATE INDEX IF NOT EXISTS idx_conversations_user ON conversations(user_id, created_at);
 
+            CREATE TABLE IF NOT EXISTS conversation_summaries (
+                user_id TEXT PRIMARY KEY REFERENCES users(id),
+                summary TEXT NOT NULL DEFAULT '',
+                summarized_through_id INTEGER NOT NULL DEFAULT 0,
+                prompt_version TEXT,
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
             CREATE TABLE IF NOT EXISTS agent_profiles (
                 user_id TEXT PRIMARY KEY REFERENCES users(id),
                 personality_summary TEXT NOT NULL DEFAULT '',
@@ -52,6 +68,90 @@ impl Database {
                 updated_at TEXT NOT NULL DEFAULT (datetime('now'))
             );
 
+            CREATE TABLE IF NOT EXISTS agent_profile_visibility (
+                user_id TEXT NOT NULL REFERENCES users(id),
+                field TEXT NOT NULL,
+                visibility TEXT NOT NULL CHECK (visibility IN ('private', 'agent_only', 'shareable')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (user_id, field)
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_personas (
+                user_id TEXT PRIMARY KEY REFERENCES users(id),
+                name TEXT NOT NULL,
+                tone TEXT NOT NULL CHECK (tone IN ('warm', 'playful', 'direct', 'calm')),
+                verbosity TEXT NOT NULL CHECK (verbosity IN ('brief', 'balanced', 'detailed')),
+                partner_questions TEXT NOT NULL CHECK (partner_questions IN ('rarely', 'sometimes', 'often')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS known_names (
+                user_id TEXT NOT NULL REFERENCES users(id),
+                name TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (user_id, name)
+            );
+
+            CREATE TABLE IF NOT EXISTS onboarding_answers (
+                user_id TEXT NOT NULL REFERENCES users(id),
+                question_id TEXT NOT NULL,
+                answer TEXT NOT NULL,
+                question_set_version INTEGER NOT NULL,
+                answered_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (user_id, question_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS match_preferences (
+                user_id TEXT PRIMARY KEY REFERENCES users(id),
+                birth_year INTEGER,
+                gender TEXT,
+                city TEXT,
+                seeking_genders TEXT NOT NULL DEFAULT '',
+                age_min INTEGER,
+                age_max INTEGER,
+                same_city_only INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_profile_versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                version INTEGER NOT NULL,
+                personality_summary TEXT NOT NULL,
+                interests TEXT NOT NULL,
+                core_values TEXT NOT NULL,
+                communication_style TEXT NOT NULL,
+                looking_for TEXT NOT NULL,
+                deal_breakers TEXT NOT NULL,
+                raw_notes TEXT NOT NULL,
+                source TEXT NOT NULL CHECK (source IN ('baseline', 'agent', 'user', 'proposal', 'rollback')),
+                conversation_from_id INTEGER,
+                conversation_to_id INTEGER,
+                restored_version INTEGER,
+                prompt_version TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(user_id, version)
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_profile_locks (
+                user_id TEXT NOT NULL REFERENCES users(id),
+                field TEXT NOT NULL,
+                locked_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (user_id, field)
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_profile_proposals (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                field TEXT NOT NULL,
+                current_value TEXT NOT NULL,
+                proposed_value TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'accepted', 'rejected', 'superseded')),
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                resolved_at TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_agent_profile_proposals_user ON agent_profile_proposals(user_id, status);
+
             CREATE TABLE IF NOT EXISTS agent_peer_notes (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 agent_user_id TEXT NOT NULL REFERENCES users(id),
@@ -60,6 +160,7 @@ impl Database {
                 notes TEXT NOT NULL DEFAULT '',
                 recommends_match INTEGER NOT NULL DEFAULT 0,
                 conversation_count INTEGER NOT NULL DEFAULT 0,
+                prompt_version TEXT,
                 updated_at TEXT NOT NULL DEFAULT (datetime('now')),
                 UNIQUE(agent_user_id, about_user_id)
             );
@@ -93,11 +194,203 @@ impl Database {
                 match_id INTEGER NOT NULL REFERENCES matches(id),
                 sender_id TEXT NOT NULL REFERENCES users(id),
                 content TEXT NOT NULL,
+                edited_at TEXT,
+                deleted_at TEXT,
                 created_at TEXT NOT NULL DEFAULT (datetime('now'))
             );
             CREATE INDEX IF NOT EXISTS idx_dm_match ON direct_messages(match_id, created_at);
+
+            CREATE TABLE IF NOT EXISTS dm_edits (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                message_id INTEGER NOT NULL REFERENCES direct_messages(id),
+                previous_content TEXT NOT NULL,
+                edited_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_dm_edits_message ON dm_edits(message_id);
+
+            CREATE TABLE IF NOT EXISTS moderation_cases (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                reporter_id TEXT NOT NULL REFERENCES users(id),
+                reported_user_id TEXT NOT NULL REFERENCES users(id),
+                target_type TEXT NOT NULL CHECK (target_type IN ('user', 'message', 'profile')),
+                message_id INTEGER,
+                reason TEXT NOT NULL,
+                details TEXT NOT NULL DEFAULT '',
+                snapshot TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'triaged', 'actioned', 'closed')),
+                priority TEXT NOT NULL DEFAULT 'normal' CHECK (priority IN ('low', 'normal', 'high')),
+                assigned_to TEXT,
+                resolution TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                closed_at TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_moderation_cases_status ON moderation_cases(status, priority, created_at);
+            CREATE INDEX IF NOT EXISTS idx_moderation_cases_reported ON moderation_cases(reported_user_id);
+
+            CREATE TABLE IF NOT EXISTS moderation_case_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                case_id INTEGER NOT NULL REFERENCES moderation_cases(id),
+                actor_id TEXT,
+                event_type TEXT NOT NULL,
+                details TEXT NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_moderation_case_events_case ON moderation_case_events(case_id);
+
+            CREATE TABLE IF NOT EXISTS moderation_flags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                context TEXT NOT NULL CHECK (context IN ('dm', 'chat')),
+                match_id INTEGER,
+                message_id INTEGER,
+                content TEXT NOT NULL,
+                action TEXT NOT NULL CHECK (action IN ('flag', 'block')),
+                category TEXT NOT NULL,
+                reason TEXT NOT NULL DEFAULT '',
+                source TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'dismissed', 'upheld')),
+                reviewed_by TEXT,
+                review_note TEXT,
+                reviewed_at TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_moderation_flags_status ON moderation_flags(status, created_at);
+
+            CREATE TABLE IF NOT EXISTS match_feedback (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                match_id INTEGER NOT NULL REFERENCES matches(id),
+                user_id TEXT NOT NULL REFERENCES users(id),
+                rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+                met_in_person INTEGER NOT NULL DEFAULT 0,
+                comment TEXT NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_match_feedback_user ON match_feedback(user_id, created_at);
+
+            CREATE TABLE IF NOT EXISTS match_icebreakers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                match_id INTEGER NOT NULL REFERENCES matches(id),
+                user_id TEXT NOT NULL REFERENCES users(id),
+                content TEXT NOT NULL,
+                prompt_version TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_icebreakers_match ON match_icebreakers(match_id, user_id);
+
+            CREATE TABLE IF NOT EXISTS dm_attachments (
+                id TEXT PRIMARY KEY,
+                message_id INTEGER NOT NULL REFERENCES direct_messages(id),
+                uploader_id TEXT NOT NULL REFERENCES users(id),
+                file_name TEXT NOT NULL,
+                content_type TEXT NOT NULL,
+                size_bytes INTEGER NOT NULL,
+                storage_key TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_dm_attachments_message ON dm_attachments(message_id);
+
+            CREATE TABLE IF NOT EXISTS dm_reactions (
+                message_id INTEGER NOT NULL REFERENCES direct_messages(id),
+                user_id TEXT NOT NULL REFERENCES users(id),
+                emoji TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (message_id, user_id, emoji)
+            );
+
+            CREATE TABLE IF NOT EXISTS dm_read_state (
+                match_id INTEGER NOT NULL REFERENCES matches(id),
+                user_id TEXT NOT NULL REFERENCES users(id),
+                last_read_message_id INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                PRIMARY KEY (match_id, user_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS username_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                old_username TEXT NOT NULL,
+                changed_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_username_history_name ON username_history(old_username COLLATE NOCASE);
+
+            CREATE TABLE IF NOT EXISTS data_exports (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                format TEXT NOT NULL CHECK(format IN ('json', 'zip')),
+                status TEXT NOT NULL DEFAULT 'pending' CHECK(status IN ('pending', 'ready', 'failed')),
+                payload BLOB,
+                error TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                completed_at TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_data_exports_user ON data_exports(user_id, created_at);
             ",
         )?;
+
+        // Columns added after the initial schema; older databases need them backfilled
+        add_column_if_missing(&conn, "users", "deletion_requested_at", "TEXT")?;
+        add_column_if_missing(&conn, "users", "deleted_at", "TEXT")?;
+        add_column_if_missing(&conn, "users", "role", "TEXT NOT NULL DEFAULT 'user' CHECK(role IN ('user', 'admin'))")?;
+        add_column_if_missing(&conn, "users", "suspended_at", "TEXT")?;
+        add_column_if_missing(&conn, "users", "banned_at", "TEXT")?;
+        add_column_if_missing(&conn, "users", "suspension_reason", "TEXT")?;
+        add_column_if_missing(&conn, "users", "onboarding_completed_at", "TEXT")?;
+        add_column_if_missing(&conn, "direct_messages", "edited_at", "TEXT")?;
+        add_column_if_missing(&conn, "direct_messages", "deleted_at", "TEXT")?;
+        add_column_if_missing(&conn, "conversations", "prompt_version", "TEXT")?;
+        add_column_if_missing(&conn, "agent_profile_versions", "prompt_version", "TEXT")?;
+        add_column_if_missing(&conn, "agent_peer_notes", "prompt_version", "TEXT")?;
+        add_column_if_missing(&conn, "conversation_summaries", "prompt_version", "TEXT")?;
+        add_column_if_missing(&conn, "match_icebreakers", "prompt_version", "TEXT")?;
+
+        // Full-text index over agent chat, kept in sync by triggers. Built from the existing
+        // rows the first time it is created.
+        let fts_exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'conversations_fts'",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute_batch(
+            "
+            CREATE VIRTUAL TABLE IF NOT EXISTS conversations_fts USING fts5(content, content='conversations', content_rowid='id');
+            CREATE TRIGGER IF NOT EXISTS conversations_fts_insert AFTER INSERT ON conversations BEGIN
+                INSERT INTO conversations_fts(rowid, content) VALUES (new.id, new.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS conversations_fts_delete AFTER DELETE ON conversations BEGIN
+                INSERT INTO conversations_fts(conversations_fts, rowid, content) VALUES ('delete', old.id, old.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS conversations_fts_update AFTER UPDATE OF content ON conversations BEGIN
+                INSERT INTO conversations_fts(conversations_fts, rowid, content) VALUES ('delete', old.id, old.content);
+                INSERT INTO conversations_fts(rowid, content) VALUES (new.id, new.content);
+            END;
+            ",
+        )?;
+        if !fts_exists {
+            conn.execute("INSERT INTO conversations_fts(conversations_fts) VALUES ('rebuild')", [])?;
+        }
+
+        // Case-insensitive uniqueness; fails on legacy rows that differ only by case
+        if let Err(e) = conn.execute_batch(
+            "
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_users_username_nocase ON users(username COLLATE NOCASE);
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_nocase ON users(lower(email));
+            ",
+        ) {
+            log::warn!("Could not create case-insensitive user indexes (duplicate accounts?): {}", e);
+        }
         Ok(())
     }
 }
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists: bool = conn
+        .prepare(&format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?1", table))?
+        .query_row(rusqlite::params![column], |row| row.get::<_, i64>(0))?
+        > 0;
+
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, definition))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,377 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use std::io::Write;
+use uuid::Uuid;
+
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+use crate::routes::{direct_message_from_row, get_agent_profile_db, load_message_details, match_feedback_from_row, DM_COLUMNS};
+use crate::onboarding::{answers_db, get_preferences_db};
+use crate::persona::get_persona_db;
+use crate::profile_history::all_versions_db;
+use crate::profile_locks::{list_proposals_db, locked_fields_db};
+use crate::visibility::get_visibility_db;
+
+/// Histories with more rows than this are exported by a background job instead of inline
+fn inline_export_max_rows() -> i64 {
+    std::env::var("EXPORT_INLINE_MAX_ROWS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5000)
+}
+
+fn parse_format(query: &ExportQuery) -> Result<String, HttpResponse> {
+    let format = query.format.clone().unwrap_or_else(|| "json".to_string());
+    if format != "json" && format != "zip" {
+        return Err(HttpResponse::BadRequest().json(serde_json::json!({"error": "format must be 'json' or 'zip'"})));
+    }
+    Ok(format)
+}
+
+/// Download the export straight away. Large histories have to go through `start_export`.
+pub async fn request_export(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<ExportQuery>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let format = match parse_format(&query) {
+        Ok(f) => f,
+        Err(e) => return e,
+    };
+
+    let export = {
+        let conn = db.conn.lock().unwrap();
+        let row_count: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM conversations WHERE user_id = ?1)
+                      + (SELECT COUNT(*) FROM direct_messages WHERE match_id IN (SELECT id FROM matches WHERE user_a_id = ?1 OR user_b_id = ?1))",
+                rusqlite::params![&claims.sub],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+        if row_count > inline_export_max_rows() {
+            return HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                "error": "Your history is too large to download directly; start a background export with POST /v1/auth/export"
+            }));
+        }
+        collect_user_data(&conn, &claims.sub)
+    };
+
+    // Serialized and zipped without holding the database lock
+    match export.and_then(|e| build_archive(&e, &format)) {
+        Ok(bytes) => archive_response(&format, bytes),
+        Err(e) => {
+            log::error!("Export failed for {}: {}", claims.sub, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to build export"}))
+        }
+    }
+}
+
+/// Queue a background export, to be fetched with `get_export`
+pub async fn start_export(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<ExportQuery>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let format = match parse_format(&query) {
+        Ok(f) => f,
+        Err(e) => return e,
+    };
+
+    let job_id = Uuid::new_v4().to_string();
+    {
+        let conn = db.conn.lock().unwrap();
+        if let Err(e) = conn.execute(
+            "INSERT INTO data_exports (id, user_id, format) VALUES (?1, ?2, ?3)",
+            rusqlite::params![&job_id, &claims.sub, &format],
+        ) {
+            log::error!("Export job error: {}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to queue export"}));
+        }
+    }
+
+    let db_clone = db.clone();
+    let jid = job_id.clone();
+    let uid = claims.sub.clone();
+    tokio::task::spawn_blocking(move || {
+        run_export_job(&db_clone, &jid, &uid, &format);
+    });
+
+    HttpResponse::Accepted().json(ExportJob {
+        id: job_id,
+        status: "pending".to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        completed_at: None,
+    })
+}
+
+pub async fn get_export(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let job_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let result = conn.query_row(
+        "SELECT format, status, payload, error, created_at, completed_at FROM data_exports WHERE id = ?1 AND user_id = ?2",
+        rusqlite::params![&job_id, &claims.sub],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<Vec<u8>>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        },
+    );
+
+    match result {
+        Ok((format, status, payload, _, _, _)) if status == "ready" => {
+            archive_response(&format, payload.unwrap_or_default())
+        }
+        Ok((_, status, _, error, created_at, completed_at)) if status == "failed" => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "id": job_id,
+                "status": status,
+                "error": error.unwrap_or_default(),
+                "created_at": created_at,
+                "completed_at": completed_at,
+            }))
+        }
+        Ok((_, status, _, _, created_at, completed_at)) => HttpResponse::Accepted().json(ExportJob {
+            id: job_id,
+            status,
+            created_at,
+            completed_at,
+        }),
+        Err(_) => HttpResponse::NotFound().json(serde_json::json!({"error": "Export not found"})),
+    }
+}
+
+fn run_export_job(db: &Database, job_id: &str, user_id: &str, format: &str) {
+    // Only the row reads hold the lock; serializing and zipping a large history doesn't
+    // stall every other request
+    let export = {
+        let conn = db.conn.lock().unwrap();
+        collect_user_data(&conn, user_id)
+    };
+    let result = export.and_then(|e| build_archive(&e, format));
+
+    let conn = db.conn.lock().unwrap();
+    match result {
+        Ok(bytes) => {
+            let _ = conn.execute(
+                "UPDATE data_exports SET status = 'ready', payload = ?1, completed_at = datetime('now') WHERE id = ?2",
+                rusqlite::params![&bytes, job_id],
+            );
+            log::info!("Export {} ready for user {}", job_id, user_id);
+        }
+        Err(e) => {
+            log::error!("Export {} failed for {}: {}", job_id, user_id, e);
+            let _ = conn.execute(
+                "UPDATE data_exports SET status = 'failed', error = ?1, completed_at = datetime('now') WHERE id = ?2",
+                rusqlite::params![&e, job_id],
+            );
+        }
+    }
+}
+
+/// Drop finished export archives after a week so personal data doesn't linger
+pub fn purge_stale_exports(conn: &rusqlite::Connection) -> usize {
+    conn.execute(
+        "DELETE FROM data_exports WHERE created_at <= datetime('now', '-7 days')",
+        [],
+    )
+    .unwrap_or(0)
+}
+
+fn archive_response(format: &str, bytes: Vec<u8>) -> HttpResponse {
+    let (content_type, filename) = if format == "zip" {
+        ("application/zip", "jupiter-export.zip")
+    } else {
+        ("application/json", "jupiter-export.json")
+    };
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+        .body(bytes)
+}
+
+fn build_archive(export: &DataExport, format: &str) -> Result<Vec<u8>, String> {
+    if format != "zip" {
+        return serde_json::to_vec_pretty(export).map_err(|e| e.to_string());
+    }
+
+    // One file per top-level section, taken from the same object as the JSON export so the
+    // two formats can't drift apart when a section is added
+    let sections = match serde_json::to_value(export).map_err(|e| e.to_string())? {
+        serde_json::Value::Object(sections) => sections,
+        _ => return Err("Export is not an object".to_string()),
+    };
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, value) in sections {
+        let data = serde_json::to_vec_pretty(&value).map_err(|e| e.to_string())?;
+        zip.start_file(format!("{}.json", name), options).map_err(|e| e.to_string())?;
+        zip.write_all(&data).map_err(|e| e.to_string())?;
+    }
+    let cursor = zip.finish().map_err(|e| e.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+fn collect_user_data(conn: &rusqlite::Connection, user_id: &str) -> Result<DataExport, String> {
+    let user = conn
+        .query_row(
+            "SELECT id, username, email, display_name, bio, created_at, updated_at, deletion_requested_at FROM users WHERE id = ?1",
+            rusqlite::params![user_id],
+            |row| {
+                Ok(UserExport {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    email: row.get(2)?,
+                    display_name: row.get(3)?,
+                    bio: row.get(4)?,
+                    created_at: row.get(5)?,
+                    updated_at: row.get(6)?,
+                    deletion_requested_at: row.get(7)?,
+                })
+            },
+        )
+        .map_err(|e| format!("User lookup failed: {}", e))?;
+
+    let conversations: Vec<ChatMessage> = conn
+        .prepare("SELECT id, role, content, created_at, prompt_version FROM conversations WHERE user_id = ?1 ORDER BY id ASC")
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![user_id], |row| {
+                Ok(ChatMessage {
+                    id: Some(row.get(0)?),
+                    role: row.get(1)?,
+                    content: row.get(2)?,
+                    prompt_version: row.get(4)?,
+                    created_at: Some(row.get(3)?),
+                })
+            })?
+            .collect()
+        })
+        .map_err(|e| e.to_string())?;
+
+    let peer_notes_about_me: Vec<AgentPeerNote> = conn
+        .prepare("SELECT id, agent_user_id, about_user_id, compatibility_score, notes, recommends_match, conversation_count, updated_at, prompt_version FROM agent_peer_notes WHERE about_user_id = ?1 ORDER BY id ASC")
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![user_id], |row| {
+                Ok(AgentPeerNote {
+                    id: row.get(0)?,
+                    agent_user_id: row.get(1)?,
+                    about_user_id: row.get(2)?,
+                    compatibility_score: row.get(3)?,
+                    notes: row.get(4)?,
+                    recommends_match: row.get::<_, i32>(5)? != 0,
+                    conversation_count: row.get(6)?,
+                    prompt_version: row.get(8)?,
+                    updated_at: row.get(7)?,
+                })
+            })?
+            .collect()
+        })
+        .map_err(|e| e.to_string())?;
+
+    let matches: Vec<MatchRecord> = conn
+        .prepare("SELECT id, user_a_id, user_b_id, agent_a_approves, agent_b_approves, is_matched, created_at, updated_at FROM matches WHERE user_a_id = ?1 OR user_b_id = ?1 ORDER BY id ASC")
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![user_id], |row| {
+                Ok(MatchRecord {
+                    id: row.get(0)?,
+                    user_a_id: row.get(1)?,
+                    user_b_id: row.get(2)?,
+                    agent_a_approves: row.get::<_, i32>(3)? != 0,
+                    agent_b_approves: row.get::<_, i32>(4)? != 0,
+                    is_matched: row.get::<_, i32>(5)? != 0,
+                    created_at: row.get(6)?,
+                    updated_at: row.get(7)?,
+                    other_user: None,
+                    unread_count: 0,
+                    last_message: None,
+                    other_last_read_message_id: 0,
+                })
+            })?
+            .collect()
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut direct_messages: Vec<DirectMessage> = conn
+        .prepare(&format!("SELECT {} FROM direct_messages WHERE match_id IN (SELECT id FROM matches WHERE user_a_id = ?1 OR user_b_id = ?1) ORDER BY id ASC", DM_COLUMNS))
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![user_id], direct_message_from_row)?
+            .collect()
+        })
+        .map_err(|e| e.to_string())?;
+    load_message_details(conn, &mut direct_messages);
+
+    let match_feedback: Vec<MatchFeedback> = conn
+        .prepare("SELECT id, match_id, rating, met_in_person, comment, created_at FROM match_feedback WHERE user_id = ?1 ORDER BY id ASC")
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![user_id], match_feedback_from_row)?
+            .collect()
+        })
+        .map_err(|e| e.to_string())?;
+
+    let notifications: Vec<Notification> = conn
+        .prepare("SELECT id, user_id, notification_type, title, message, related_user_id, is_read, created_at FROM notifications WHERE user_id = ?1 ORDER BY id ASC")
+        .and_then(|mut stmt| {
+            stmt.query_map(rusqlite::params![user_id], |row| {
+                Ok(Notification {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    notification_type: row.get(2)?,
+                    title: row.get(3)?,
+                    message: row.get(4)?,
+                    related_user_id: row.get(5)?,
+                    is_read: row.get::<_, i32>(6)? != 0,
+                    created_at: row.get(7)?,
+                })
+            })?
+            .collect()
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok(DataExport {
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        user,
+        conversations,
+        agent_profile: get_agent_profile_db(conn, user_id),
+        profile_visibility: get_visibility_db(conn, user_id),
+        agent_persona: get_persona_db(conn, user_id),
+        onboarding_answers: answers_db(conn, user_id),
+        match_preferences: get_preferences_db(conn, user_id),
+        locked_profile_fields: locked_fields_db(conn, user_id),
+        profile_proposals: list_proposals_db(conn, user_id, false),
+        profile_history: all_versions_db(conn, user_id),
+        conversation_summary: conn
+            .query_row("SELECT summary FROM conversation_summaries WHERE user_id = ?1", rusqlite::params![user_id], |row| row.get(0))
+            .ok(),
+        peer_notes_about_me,
+        matches,
+        direct_messages,
+        match_feedback,
+        notifications,
+    })
+}
@@ -0,0 +1,187 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::agent::LlmAgent;
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+use crate::realtime::match_participants;
+use crate::redaction::redact_known_names;
+use crate::routes::get_agent_profile_db;
+use crate::visibility::{get_visibility_db, known_names_db, profile_field, profile_for, visibility_of, Audience, PROFILE_FIELDS};
+
+const ICEBREAKERS_PER_SIDE: usize = 3;
+
+/// What `agent_user_id`'s agent wrote about the other user, cleaned up for the opener prompt.
+/// An agent evaluates with its own user's agent-only fields and private match feedback, so
+/// sentences that repeat either are dropped, and both users' names are redacted.
+fn shareable_peer_notes(conn: &rusqlite::Connection, agent_user_id: &str, about_user_id: &str) -> String {
+    let notes: String = conn
+        .query_row(
+            "SELECT notes FROM agent_peer_notes WHERE agent_user_id = ?1 AND about_user_id = ?2",
+            rusqlite::params![agent_user_id, about_user_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+    if notes.is_empty() {
+        return notes;
+    }
+
+    let mut hidden = private_phrases(conn, agent_user_id);
+    let mut stmt = conn
+        .prepare("SELECT comment FROM match_feedback WHERE user_id = ?1")
+        .unwrap();
+    let comments: Vec<String> = stmt
+        .query_map(rusqlite::params![agent_user_id], |row| row.get(0))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    hidden.extend(comments.iter().flat_map(|c| phrases(c)));
+
+    let mut names = known_names_db(conn, agent_user_id);
+    names.extend(known_names_db(conn, about_user_id));
+    sanitize_notes(&notes, &hidden, &names)
+}
+
+/// `notes` without the sentences that contain any of the `hidden` phrases, and with `names` redacted
+fn sanitize_notes(notes: &str, hidden: &[String], names: &[String]) -> String {
+    let kept: String = notes
+        .split_inclusive(['.', '!', '?', '\n'])
+        .filter(|sentence| !leaks_private_content(sentence, hidden))
+        .collect();
+    redact_known_names(kept.trim(), names)
+}
+
+/// What goes into the opener prompt about a user: their shareable fields, minus deal breakers
+fn public_profile(conn: &rusqlite::Connection, user_id: &str) -> AgentProfile {
+    let mut profile = profile_for(conn, user_id, Audience::OtherAgent);
+    profile.deal_breakers.clear();
+    profile
+}
+
+/// Phrases from the fields a user hasn't made shareable. Nothing private is put in the opener
+/// prompt, so this is only a backstop against an opener that quotes one anyway.
+fn private_phrases(conn: &rusqlite::Connection, user_id: &str) -> Vec<String> {
+    let profile = get_agent_profile_db(conn, user_id);
+    let visibility = get_visibility_db(conn, user_id);
+    PROFILE_FIELDS
+        .iter()
+        .filter(|field| visibility_of(&visibility, field) != "shareable")
+        .flat_map(|field| phrases(profile_field(&profile, field)))
+        .collect()
+}
+
+/// The fragments of `text` long enough to recognise when they're repeated elsewhere
+fn phrases(text: &str) -> Vec<String> {
+    text.split([',', '.', ';', '\n'])
+        .map(|p| p.trim().to_lowercase())
+        // Very short fragments ("no", "n/a") would match almost anything
+        .filter(|p| p.chars().count() >= 8)
+        .collect()
+}
+
+fn leaks_private_content(opener: &str, private: &[String]) -> bool {
+    let opener = opener.to_lowercase();
+    private.iter().any(|p| opener.contains(p.as_str()))
+}
+
+/// Write openers for both sides of a freshly confirmed match. Runs in the background so
+/// `run_matching` isn't held up by the extra LLM calls.
+pub async fn generate_icebreakers_bg(db: web::Data<Database>, agent: web::Data<LlmAgent>, match_id: i64) {
+    let context = {
+        let conn = db.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT user_a_id, user_b_id FROM matches WHERE id = ?1 AND is_matched = 1",
+            rusqlite::params![match_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .ok()
+        .map(|(a, b)| {
+            let side_a = (public_profile(&conn, &a), shareable_peer_notes(&conn, &a, &b));
+            let side_b = (public_profile(&conn, &b), shareable_peer_notes(&conn, &b, &a));
+            let mut private = private_phrases(&conn, &a);
+            private.extend(private_phrases(&conn, &b));
+            let mut known_names = known_names_db(&conn, &a);
+            known_names.extend(known_names_db(&conn, &b));
+            (a, b, side_a, side_b, private, known_names)
+        })
+    };
+
+    let Some((user_a, user_b, side_a, side_b, private, known_names)) = context else {
+        return;
+    };
+
+    for (user_id, (my_profile, my_notes), (their_profile, their_notes)) in [(&user_a, &side_a, &side_b), (&user_b, &side_b, &side_a)] {
+        let (openers, prompt_version) = match agent.generate_icebreakers(my_profile, their_profile, my_notes, their_notes, &known_names).await {
+            Ok(o) => o,
+            Err(e) => {
+                log::error!("Icebreaker generation failed for match {} ({}): {}", match_id, user_id, e);
+                continue;
+            }
+        };
+
+        let conn = db.conn.lock().unwrap();
+        let mut saved = 0;
+        for opener in openers.iter().filter(|o| !leaks_private_content(o, &private)).take(ICEBREAKERS_PER_SIDE) {
+            if conn
+                .execute(
+                    "INSERT INTO match_icebreakers (match_id, user_id, content, prompt_version) VALUES (?1, ?2, ?3, ?4)",
+                    rusqlite::params![match_id, user_id, opener, &prompt_version],
+                )
+                .is_ok()
+            {
+                saved += 1;
+            }
+        }
+        log::info!("🧊 Saved {} icebreakers for {} in match {}", saved, user_id, match_id);
+    }
+}
+
+pub async fn get_icebreakers(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let match_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    if match_participants(&conn, match_id, &claims.sub).is_none() {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}));
+    }
+
+    // Each side only ever sees the openers written for them
+    let mut stmt = conn
+        .prepare("SELECT id, content, created_at, prompt_version FROM match_icebreakers WHERE match_id = ?1 AND user_id = ?2 ORDER BY id ASC")
+        .unwrap();
+    let icebreakers: Vec<Icebreaker> = stmt
+        .query_map(rusqlite::params![match_id, &claims.sub], |row| {
+            Ok(Icebreaker {
+                id: row.get(0)?,
+                content: row.get(1)?,
+                prompt_version: row.get(3)?,
+                created_at: row.get(2)?,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(icebreakers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_lose_hidden_sentences_and_names() {
+        let hidden = phrases("Recently divorced, in therapy");
+        let names = vec!["Anna Kowalski".to_string()];
+        let notes = "Anna Kowalski loves climbing. Like my client, she was recently divorced! Great fit overall.";
+        assert_eq!(sanitize_notes(notes, &hidden, &names), "[NAME] loves climbing. Great fit overall.");
+        assert_eq!(sanitize_notes("Nothing to hide.", &[], &[]), "Nothing to hide.");
+    }
+}
@@ -1,11 +1,32 @@
+mod admin;
 mod agent;
+mod attachments;
 mod auth;
+mod broker;
+mod budget;
 mod db;
+mod emoji;
+mod export;
+mod icebreakers;
+mod memory;
 mod models;
+mod moderation;
+mod onboarding;
+mod pagination;
+mod persona;
+mod password;
+mod profile_history;
+mod profile_locks;
+mod prompts;
+mod realtime;
+mod redaction;
+mod reports;
 mod routes;
+mod storage;
+mod visibility;
 
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer, middleware};
+use actix_web::{web, App, HttpServer};
 use std::sync::Arc;
 
 #[actix_web::main]
@@ -24,12 +45,42 @@ async fn main() -> std::io::Result<()> {
     log::info!("📂 Database: {}", db_path);
 
     let database = db::Database::new(&db_path).expect("Failed to initialize database");
+    admin::bootstrap_admins(&database);
     let db_data = web::Data::new(database);
 
+    let attachments_dir = std::env::var("ATTACHMENTS_DIR").unwrap_or_else(|_| "attachments".to_string());
+    let blob_store: Arc<dyn storage::BlobStore> =
+        Arc::new(storage::LocalBlobStore::new(&attachments_dir).expect("Failed to initialize attachment storage"));
+    let blob_data: web::Data<dyn storage::BlobStore> = web::Data::from(blob_store);
+    log::info!("📎 Attachments: {}", attachments_dir);
+
+    let broker: Arc<dyn broker::Broker> = Arc::new(broker::InProcessBroker::new());
+    let broker_data: web::Data<dyn broker::Broker> = web::Data::from(broker);
+
+    // Purge accounts whose deletion grace period has elapsed, and stale data exports
+    let purge_db = db_data.clone();
+    let purge_blobs = blob_data.clone();
+    actix_rt::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let conn = purge_db.conn.lock().unwrap();
+            let purged = auth::purge_expired_accounts(&conn, purge_blobs.get_ref(), auth::account_deletion_grace_days());
+            if purged > 0 {
+                log::info!("🗑️ Purged {} deleted account(s)", purged);
+            }
+            export::purge_stale_exports(&conn);
+        }
+    });
+
     let llm_agent = agent::LlmAgent::new();
     let agent_data = web::Data::new(llm_agent);
 
     log::info!("🤖 LLM Agent initialized");
+
+    let moderator = moderation::moderator_from_env(agent_data.clone().into_inner());
+    let moderator_data: web::Data<dyn moderation::Moderator> = web::Data::from(moderator);
+
     log::info!("🚀 Server ready at http://{}:{}", host, port);
 
     HttpServer::new(move || {
@@ -43,28 +94,85 @@ async fn main() -> std::io::Result<()> {
             .wrap(cors)
             .app_data(db_data.clone())
             .app_data(agent_data.clone())
+            .app_data(broker_data.clone())
+            .app_data(blob_data.clone())
+            .app_data(moderator_data.clone())
             .app_data(web::JsonConfig::default().limit(1024 * 1024))
             // Auth routes
             .route("/v1/auth/register", web::post().to(auth::register))
             .route("/v1/auth/login", web::post().to(auth::login))
             .route("/v1/auth/profile", web::get().to(auth::get_profile))
             .route("/v1/auth/profile", web::put().to(auth::update_profile))
+            .route("/v1/auth/username", web::put().to(auth::change_username))
+            .route("/v1/auth/password", web::put().to(auth::change_password))
+            .route("/v1/auth/account", web::delete().to(auth::delete_account))
+            .route("/v1/auth/export", web::get().to(export::request_export))
+            .route("/v1/auth/export", web::post().to(export::start_export))
+            .route("/v1/auth/export/{job_id}", web::get().to(export::get_export))
+            .route("/v1/users/{username}", web::get().to(auth::resolve_username))
             // Chat with personal agent
             .route("/v1/chat", web::get().to(routes::get_chat_history))
             .route("/v1/chat", web::post().to(routes::send_message))
             // Agent profile (what agent knows)
             .route("/v1/agent/profile", web::get().to(routes::get_agent_profile))
+            .route("/v1/agent/profile", web::put().to(profile_locks::update_agent_profile))
+            .route("/v1/agent/profile/history", web::get().to(profile_history::get_profile_history))
+            .route("/v1/agent/profile/history/{version}/rollback", web::post().to(profile_history::rollback_profile))
+            .route("/v1/agent/profile/proposals/{id}/{decision}", web::post().to(profile_locks::resolve_profile_proposal))
             .route("/v1/agent/profile/update", web::post().to(routes::trigger_profile_update))
+            .route("/v1/agent/profile/visibility", web::get().to(visibility::get_profile_visibility))
+            .route("/v1/agent/profile/visibility", web::put().to(visibility::update_profile_visibility))
+            .route("/v1/agent/profile/disclosure", web::get().to(visibility::get_profile_disclosure))
+            .route("/v1/agent/persona", web::get().to(persona::get_agent_persona))
+            .route("/v1/agent/persona", web::put().to(persona::update_agent_persona))
+            .route("/v1/agent/persona/presets", web::get().to(persona::list_persona_presets))
+            // Onboarding
+            .route("/v1/onboarding", web::get().to(onboarding::get_onboarding))
+            .route("/v1/onboarding", web::post().to(onboarding::submit_onboarding))
             // Matching
             .route("/v1/matching/trigger", web::post().to(routes::trigger_matching))
             .route("/v1/matches", web::get().to(routes::get_matches))
+            .route("/v1/matches/{id}/feedback", web::post().to(routes::submit_match_feedback))
+            .route("/v1/matches/{id}/feedback", web::get().to(routes::get_match_feedback))
+            .route("/v1/matches/{id}/icebreakers", web::get().to(icebreakers::get_icebreakers))
             // Notifications
             .route("/v1/notifications", web::get().to(routes::get_notifications))
             .route("/v1/notifications/unread", web::get().to(routes::get_unread_count))
+            .route("/v1/notifications/stream", web::get().to(realtime::notification_stream))
             .route("/v1/notifications/{id}/read", web::post().to(routes::mark_notification_read))
             // Direct messages
             .route("/v1/messages/{match_id}", web::get().to(routes::get_direct_messages))
             .route("/v1/messages/{match_id}", web::post().to(routes::send_direct_message))
+            .route("/v1/messages/{match_id}/suggest", web::post().to(routes::suggest_replies))
+            .route("/v1/messages/{match_id}/attachments", web::post().to(attachments::upload_attachments))
+            .route("/v1/attachments/{id}", web::get().to(attachments::get_attachment))
+            .route("/v1/messages/{match_id}/read", web::post().to(routes::mark_messages_read))
+            .route("/v1/messages/{match_id}/{message_id}", web::put().to(routes::edit_direct_message))
+            .route("/v1/messages/{match_id}/{message_id}", web::delete().to(routes::delete_direct_message))
+            .route("/v1/messages/{match_id}/{message_id}/history", web::get().to(routes::get_direct_message_history))
+            .route("/v1/messages/{match_id}/{message_id}/reactions", web::post().to(routes::add_reaction))
+            .route("/v1/messages/{match_id}/{message_id}/reactions/{emoji}", web::delete().to(routes::remove_reaction))
+            // Reports
+            .route("/v1/reports", web::post().to(reports::create_report))
+            .route("/v1/reports", web::get().to(reports::list_my_reports))
+            // Real-time
+            .route("/v1/ws", web::get().to(realtime::ws_connect))
+            // Admin
+            .route("/v1/admin/users", web::get().to(admin::list_users))
+            .route("/v1/admin/users/{id}", web::get().to(admin::get_user))
+            .route("/v1/admin/users/{id}/suspend", web::post().to(admin::suspend_user))
+            .route("/v1/admin/users/{id}/unsuspend", web::post().to(admin::unsuspend_user))
+            .route("/v1/admin/users/{id}/role", web::put().to(admin::set_user_role))
+            .route("/v1/admin/users/{id}/profile/rebuild", web::post().to(admin::rebuild_profile))
+            .route("/v1/admin/users/{id}/matching", web::post().to(admin::rerun_matching))
+            .route("/v1/admin/stats/matching", web::get().to(admin::matching_stats))
+            .route("/v1/admin/moderation/flags", web::get().to(admin::list_moderation_flags))
+            .route("/v1/admin/moderation/flags/{id}/review", web::post().to(admin::review_moderation_flag))
+            .route("/v1/admin/cases", web::get().to(admin::list_cases))
+            .route("/v1/admin/cases/{id}", web::get().to(admin::get_case))
+            .route("/v1/admin/cases/{id}/triage", web::post().to(admin::triage_case))
+            .route("/v1/admin/cases/{id}/action", web::post().to(admin::case_action))
+            .route("/v1/admin/cases/{id}/close", web::post().to(admin::close_case))
     })
     .bind(format!("{}:{}", host, port))?
     .run()
@@ -0,0 +1,157 @@
+use actix_web::web;
+
+use crate::agent::LlmAgent;
+use crate::db::Database;
+use crate::models::*;
+use crate::visibility::known_names_db;
+
+/// Turns sent to the LLM verbatim; anything older is covered by the summary and recall
+pub const RECENT_MESSAGES: i64 = 20;
+/// How many older messages may pile up outside the summary before it is rolled forward
+const SUMMARY_BATCH: i64 = 20;
+/// Most older messages recalled for a single reply
+const RECALL_LIMIT: i64 = 5;
+
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "are", "but", "not", "you", "your", "with", "have", "this", "that", "was", "what", "when",
+    "where", "who", "why", "how", "can", "could", "would", "should", "about", "from", "they", "them", "their", "there",
+    "then", "than", "just", "like", "really", "been", "were", "will", "some", "any", "all", "our", "out", "its",
+    "also", "into", "more", "very", "much", "too", "yes", "yeah", "okay", "did", "does", "doing", "got", "get",
+];
+
+fn chat_message_from_row(row: &rusqlite::Row) -> rusqlite::Result<ChatMessage> {
+    Ok(ChatMessage {
+        id: Some(row.get(0)?),
+        role: row.get(1)?,
+        content: row.get(2)?,
+        prompt_version: row.get(4)?,
+        created_at: Some(row.get(3)?),
+    })
+}
+
+/// The most recent chat turns, oldest first
+pub fn recent_messages(conn: &rusqlite::Connection, user_id: &str, limit: i64) -> Vec<ChatMessage> {
+    let mut stmt = conn
+        .prepare("SELECT id, role, content, created_at, prompt_version FROM conversations WHERE user_id = ?1 ORDER BY id DESC LIMIT ?2")
+        .unwrap();
+    let mut messages: Vec<ChatMessage> = stmt
+        .query_map(rusqlite::params![user_id, limit], chat_message_from_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    messages.reverse();
+    messages
+}
+
+/// Turn free text into an FTS5 query: distinctive words, each quoted, any of them matching
+fn fts_query(text: &str) -> Option<String> {
+    let mut words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.to_lowercase())
+        .filter(|w| w.chars().count() >= 3 && !STOPWORDS.contains(&w.as_str()))
+        .collect();
+    words.sort();
+    words.dedup();
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.iter().map(|w| format!("\"{}\"", w)).collect::<Vec<_>>().join(" OR "))
+}
+
+/// Summary plus older messages relevant to `query_text`. Only messages older than
+/// `before_id` are recalled, so nothing already in the recent window is repeated.
+pub fn load_memory(conn: &rusqlite::Connection, user_id: &str, query_text: &str, before_id: i64) -> ConversationMemory {
+    let summary: String = conn
+        .query_row(
+            "SELECT summary FROM conversation_summaries WHERE user_id = ?1",
+            rusqlite::params![user_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+
+    let mut recalled: Vec<ChatMessage> = match fts_query(query_text) {
+        Some(query) => conn
+            .prepare(
+                "SELECT c.id, c.role, c.content, c.created_at, c.prompt_version FROM conversations_fts f JOIN conversations c ON c.id = f.rowid
+                 WHERE conversations_fts MATCH ?1 AND c.user_id = ?2 AND c.id < ?3
+                 ORDER BY f.rank LIMIT ?4",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(rusqlite::params![query, user_id, before_id, RECALL_LIMIT], chat_message_from_row)?
+                    .collect()
+            })
+            .unwrap_or_else(|e| {
+                log::warn!("Conversation recall failed for {}: {}", user_id, e);
+                Vec::new()
+            }),
+        None => Vec::new(),
+    };
+
+    recalled.sort_by_key(|m| m.id);
+    ConversationMemory { summary, recalled }
+}
+
+/// Fold messages that have slid out of the recent window into the user's rolling summary,
+/// once enough of them have accumulated
+pub async fn summarize_bg(db: web::Data<Database>, agent: web::Data<LlmAgent>, user_id: String) {
+    let (summary, through_id, older, known_names) = {
+        let conn = db.conn.lock().unwrap();
+        let (summary, through_id): (String, i64) = conn
+            .query_row(
+                "SELECT summary, summarized_through_id FROM conversation_summaries WHERE user_id = ?1",
+                rusqlite::params![&user_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap_or_default();
+
+        // Everything after the summary except the recent window
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, role, content, created_at, prompt_version FROM conversations
+                 WHERE user_id = ?1 AND id > ?2
+                 AND id < (SELECT MIN(id) FROM (SELECT id FROM conversations WHERE user_id = ?1 ORDER BY id DESC LIMIT ?3))
+                 ORDER BY id ASC",
+            )
+            .unwrap();
+        let older: Vec<ChatMessage> = stmt
+            .query_map(rusqlite::params![&user_id, through_id, RECENT_MESSAGES], chat_message_from_row)
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+        (summary, through_id, older, known_names_db(&conn, &user_id))
+    };
+
+    if (older.len() as i64) < SUMMARY_BATCH {
+        return;
+    }
+    let (updated, used, prompt_version) = match agent.summarize_conversation(&summary, &older, &known_names).await {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("Conversation summary failed for {}: {}", user_id, e);
+            return;
+        }
+    };
+    // Messages that didn't fit in the prompt stay unsummarized for the next run
+    let Some(last_id) = older.get(used.saturating_sub(1)).and_then(|m| m.id) else {
+        return;
+    };
+
+    let conn = db.conn.lock().unwrap();
+    // Only move forward from the point we read; a concurrent run may already have
+    let result = if through_id == 0 {
+        conn.execute(
+            "INSERT OR IGNORE INTO conversation_summaries (user_id, summary, summarized_through_id, prompt_version) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![&user_id, &updated, last_id, &prompt_version],
+        )
+    } else {
+        conn.execute(
+            "UPDATE conversation_summaries SET summary = ?1, summarized_through_id = ?2, prompt_version = ?3, updated_at = datetime('now') WHERE user_id = ?4 AND summarized_through_id = ?5",
+            rusqlite::params![&updated, last_id, &prompt_version, &user_id, through_id],
+        )
+    };
+    match result {
+        Ok(n) if n > 0 => log::info!("🧠 Summarized {} older messages for {}", used, user_id),
+        Ok(_) => {}
+        Err(e) => log::error!("Failed to save conversation summary for {}: {}", user_id, e),
+    }
+}
@@ -1,6 +1,23 @@
-use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
+// ── Pagination ──
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageQuery {
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Continue in the same direction (`before` when paging back, `after` when paging forward)
+    pub next_cursor: Option<String>,
+    /// Cursor of the first item scanned, for paging the other way
+    pub prev_cursor: Option<String>,
+}
+
 // ── Auth ──
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -13,6 +30,8 @@ pub struct RegisterRequest {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
+    /// Username or email, matched case-insensitively
+    #[serde(alias = "identifier", alias = "email")]
     pub username: String,
     pub password: String,
 }
@@ -33,13 +52,33 @@ pub struct UserPublic {
     pub created_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeUsernameRequest {
+    pub username: String,
+}
+
+/// Public handle returned when resolving an @-reference
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserHandle {
+    pub id: String,
+    pub username: String,
+    pub display_name: String,
+    pub renamed_from: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // user_id
     pub username: String,
+    #[serde(default = "default_role")]
+    pub role: String, // "user" | "admin"
     pub exp: usize,
 }
 
+fn default_role() -> String {
+    "user".to_string()
+}
+
 // ── Chat ──
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -47,9 +86,20 @@ pub struct ChatMessage {
     pub id: Option<i64>,
     pub role: String,
     pub content: String,
+    /// Prompt template that produced an agent reply, e.g. `chat@1`
+    pub prompt_version: Option<String>,
     pub created_at: Option<String>,
 }
 
+/// What the agent remembers beyond the recent turns it is sent verbatim
+#[derive(Debug, Clone, Default)]
+pub struct ConversationMemory {
+    /// Rolling summary of everything older than the recent window
+    pub summary: String,
+    /// Older messages that look relevant to the current one
+    pub recalled: Vec<ChatMessage>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SendMessageRequest {
     pub content: String,
@@ -76,6 +126,228 @@ pub struct AgentProfile {
     pub updated_at: String,
 }
 
+/// The profile as its owner sees it, with their locks and the agent's pending suggestions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditableAgentProfile {
+    #[serde(flatten)]
+    pub profile: AgentProfile,
+    /// Fields the background updater may not overwrite
+    pub locked_fields: Vec<String>,
+    pub pending_proposals: Vec<ProfileProposal>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateAgentProfileRequest {
+    /// Lock or unlock fields; edited fields are locked unless listed here as false
+    pub locked: Option<std::collections::HashMap<String, bool>>,
+    /// New values keyed by profile field name; a missing or null field is left as it is
+    #[serde(flatten)]
+    pub fields: std::collections::HashMap<String, Option<String>>,
+}
+
+/// A change the agent wanted to make to a locked field
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileProposal {
+    pub id: i64,
+    pub field: String,
+    pub current_value: String,
+    pub proposed_value: String,
+    /// pending, accepted, rejected or superseded
+    pub status: String,
+    pub created_at: String,
+    pub resolved_at: Option<String>,
+}
+
+/// One saved revision of an agent profile, described by what changed from the revision before
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileVersion {
+    pub id: i64,
+    pub version: i64,
+    /// agent, user, proposal, rollback or baseline
+    pub source: String,
+    /// Chat messages the agent read to produce this revision (agent revisions only)
+    pub conversation_from_id: Option<i64>,
+    pub conversation_to_id: Option<i64>,
+    /// For rollbacks, the version that was restored
+    pub restored_version: Option<i64>,
+    /// For agent revisions, the prompt template that produced them
+    pub prompt_version: Option<String>,
+    pub changes: Vec<ProfileFieldChange>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileFieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// Who may see each profile field: "private" (only your own chats with your agent),
+/// "agent_only" (your agent may use it when evaluating others for you, but never discloses
+/// it) or "shareable" (shown to the agents of potential matches)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileVisibility {
+    pub personality_summary: String,
+    pub interests: String,
+    pub core_values: String,
+    pub communication_style: String,
+    pub looking_for: String,
+    pub deal_breakers: String,
+    pub raw_notes: String,
+}
+
+impl Default for ProfileVisibility {
+    fn default() -> Self {
+        ProfileVisibility {
+            personality_summary: "shareable".to_string(),
+            interests: "shareable".to_string(),
+            core_values: "shareable".to_string(),
+            communication_style: "shareable".to_string(),
+            looking_for: "shareable".to_string(),
+            deal_breakers: "agent_only".to_string(),
+            raw_notes: "private".to_string(),
+        }
+    }
+}
+
+/// Exactly what your agent discloses about you during matching
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProfileDisclosure {
+    pub visibility: ProfileVisibility,
+    /// Fields and values shown to a potential match's agent
+    pub shared: std::collections::BTreeMap<String, String>,
+    /// Fields your own agent weighs when evaluating others, without revealing them
+    pub agent_only: std::collections::BTreeMap<String, String>,
+    /// Fields that never leave your own conversations
+    pub private: Vec<String>,
+    /// The profile block as it appears in another agent's prompt, after redaction
+    pub prompt_preview: String,
+}
+
+// ── Agent Persona ──
+
+/// How the user's own agent presents itself in chat
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentPersona {
+    pub name: String,
+    /// warm, playful, direct or calm
+    pub tone: String,
+    /// brief, balanced or detailed
+    pub verbosity: String,
+    /// How often the agent brings up what the user wants in a partner: rarely, sometimes or often
+    pub partner_questions: String,
+    pub updated_at: Option<String>,
+}
+
+impl Default for AgentPersona {
+    fn default() -> Self {
+        AgentPersona {
+            name: "Jupiter".to_string(),
+            tone: "warm".to_string(),
+            verbosity: "balanced".to_string(),
+            partner_questions: "sometimes".to_string(),
+            updated_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateAgentPersonaRequest {
+    /// Start from a preset; the other fields override it
+    pub preset: Option<String>,
+    pub name: Option<String>,
+    pub tone: Option<String>,
+    pub verbosity: Option<String>,
+    pub partner_questions: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PersonaPreset {
+    pub id: String,
+    pub description: String,
+    pub persona: AgentPersona,
+}
+
+// ── Onboarding ──
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OnboardingOption {
+    pub value: String,
+    pub label: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OnboardingQuestion {
+    pub id: String,
+    pub prompt: String,
+    /// text, number, choice, multi_choice or boolean
+    pub kind: String,
+    pub options: Vec<OnboardingOption>,
+    pub required: bool,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    /// Agent profile field the answer is written into, if any
+    pub profile_field: Option<String>,
+    /// Whether the answer is a hard filter applied before agents evaluate anyone
+    pub hard_filter: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OnboardingSection {
+    pub id: String,
+    pub title: String,
+    pub questions: Vec<OnboardingQuestion>,
+}
+
+/// Who a user is and who they'll consider, as used by the hard filters in matching
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MatchPreferences {
+    pub birth_year: Option<i64>,
+    pub gender: Option<String>,
+    pub city: Option<String>,
+    /// Empty means anyone
+    pub seeking_genders: Vec<String>,
+    pub age_min: Option<i64>,
+    pub age_max: Option<i64>,
+    pub same_city_only: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OnboardingState {
+    pub question_set_version: i64,
+    pub sections: Vec<OnboardingSection>,
+    pub answers: std::collections::BTreeMap<String, serde_json::Value>,
+    /// Set once every required question has an answer
+    pub completed_at: Option<String>,
+    pub preferences: MatchPreferences,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubmitOnboardingRequest {
+    /// Question id to answer; `null` or an empty string clears an answer
+    pub answers: std::collections::HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OnboardingResult {
+    #[serde(flatten)]
+    pub state: OnboardingState,
+    /// Profile fields rewritten from the answers
+    pub seeded_fields: Vec<String>,
+    /// Profile fields left alone because they were changed since onboarding last wrote them
+    pub kept_fields: Vec<String>,
+}
+
+/// What the chat agent already knows from onboarding
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct OnboardingContext {
+    /// Titles of the sections the user answered
+    pub topics: Vec<String>,
+    /// The hard filters in words, empty when none are set
+    pub preferences: String,
+}
+
 // ── Agent Peer Notes ──
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -87,6 +359,8 @@ pub struct AgentPeerNote {
     pub notes: String,
     pub recommends_match: bool,
     pub conversation_count: i32,
+    /// Prompt template behind the latest evaluation
+    pub prompt_version: Option<String>,
     pub updated_at: String,
 }
 
@@ -103,6 +377,38 @@ pub struct MatchRecord {
     pub created_at: String,
     pub updated_at: String,
     pub other_user: Option<UserPublic>,
+    /// DMs from the other participant newer than your read marker
+    pub unread_count: i64,
+    pub last_message: Option<DirectMessage>,
+    /// How far the other participant has read, for "seen" indicators
+    pub other_last_read_message_id: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatchFeedbackRequest {
+    /// 1 (not for me) to 5 (it went great)
+    pub rating: i32,
+    pub met_in_person: Option<bool>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MatchFeedback {
+    pub id: i64,
+    pub match_id: i64,
+    pub rating: i32,
+    pub met_in_person: bool,
+    pub comment: String,
+    pub created_at: String,
+}
+
+/// Past feedback paired with what the user's agent thought of that match beforehand
+#[derive(Debug, Clone)]
+pub struct FeedbackEvidence {
+    pub rating: i32,
+    pub met_in_person: bool,
+    pub comment: String,
+    pub agent_notes: String,
 }
 
 // ── Notifications ──
@@ -128,6 +434,47 @@ pub struct DirectMessage {
     pub sender_id: String,
     pub content: String,
     pub created_at: String,
+    pub edited_at: Option<String>,
+    pub is_deleted: bool,
+    pub deleted_at: Option<String>,
+    pub reactions: Vec<DmReaction>,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DmReaction {
+    pub user_id: String,
+    pub emoji: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub id: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub url: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Icebreaker {
+    pub id: i64,
+    pub content: String,
+    pub prompt_version: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DmEdit {
+    pub previous_content: String,
+    pub edited_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReactionRequest {
+    pub emoji: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -135,6 +482,54 @@ pub struct SendDirectMessageRequest {
     pub content: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestRepliesRequest {
+    /// Optional steer, e.g. "suggest meeting for coffee"
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplySuggestions {
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MarkReadRequest {
+    /// Defaults to the latest message in the thread
+    pub message_id: Option<i64>,
+}
+
+// ── Real-time ──
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenQuery {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationStreamQuery {
+    pub last_event_id: Option<i64>,
+}
+
+/// Server → client events pushed over the real-time channel
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RealtimeEvent {
+    Message { message: DirectMessage },
+    MessageUpdated { message: DirectMessage },
+    Typing { match_id: i64, user_id: String, is_typing: bool },
+    Presence { user_id: String, online: bool },
+    Notification { notification: Notification },
+    Read { match_id: i64, user_id: String, last_read_message_id: i64 },
+}
+
+/// Client → server events received over the WebSocket
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    Typing { match_id: i64, is_typing: bool },
+}
+
 // ── LLM Types ──
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -169,6 +564,249 @@ pub struct UpdateProfileRequest {
     pub bio: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+// ── Data Export ──
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportJob {
+    pub id: String,
+    pub status: String,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserExport {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub display_name: String,
+    pub bio: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub deletion_requested_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataExport {
+    pub exported_at: String,
+    pub user: UserExport,
+    pub conversations: Vec<ChatMessage>,
+    pub agent_profile: AgentProfile,
+    pub profile_visibility: ProfileVisibility,
+    pub agent_persona: AgentPersona,
+    pub onboarding_answers: std::collections::BTreeMap<String, serde_json::Value>,
+    pub match_preferences: MatchPreferences,
+    pub locked_profile_fields: Vec<String>,
+    pub profile_proposals: Vec<ProfileProposal>,
+    pub profile_history: Vec<ProfileVersion>,
+    pub conversation_summary: Option<String>,
+    pub peer_notes_about_me: Vec<AgentPeerNote>,
+    pub matches: Vec<MatchRecord>,
+    pub direct_messages: Vec<DirectMessage>,
+    pub match_feedback: Vec<MatchFeedback>,
+    pub notifications: Vec<Notification>,
+}
+
+// ── Admin ──
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminUserQuery {
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AdminUserSummary {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub display_name: String,
+    pub role: String,
+    pub created_at: String,
+    pub suspended_at: Option<String>,
+    pub suspension_reason: Option<String>,
+    pub banned_at: Option<String>,
+    pub deletion_requested_at: Option<String>,
+    pub chat_message_count: i64,
+    pub confirmed_match_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminUserDetail {
+    pub user: AdminUserSummary,
+    pub agent_profile: AgentProfile,
+    pub matches: Vec<MatchRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuspendUserRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetRoleRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatchingStats {
+    pub total_users: i64,
+    pub suspended_users: i64,
+    pub profiled_users: i64,
+    pub peer_evaluations: i64,
+    pub recommendations: i64,
+    pub average_compatibility: f64,
+    pub pending_proposals: i64,
+    pub confirmed_matches: i64,
+    pub direct_messages: i64,
+}
+
+// ── Moderation ──
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModerationFlagQuery {
+    /// pending (default), dismissed, upheld or all
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModerationFlag {
+    pub id: i64,
+    pub user_id: String,
+    pub username: String,
+    pub context: String,
+    pub match_id: Option<i64>,
+    pub message_id: Option<i64>,
+    pub content: String,
+    pub action: String,
+    pub category: String,
+    pub reason: String,
+    pub source: String,
+    pub status: String,
+    pub reviewed_by: Option<String>,
+    pub review_note: Option<String>,
+    pub reviewed_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewFlagRequest {
+    /// "dismiss" or "uphold"
+    pub decision: String,
+    /// When upholding a DM flag, also delete the message
+    pub remove_content: Option<bool>,
+    pub note: Option<String>,
+}
+
+// ── Reports & Cases ──
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateReportRequest {
+    /// "user", "message" or "profile"
+    pub target_type: String,
+    /// Required for user and profile reports
+    pub user_id: Option<String>,
+    /// Required for message reports
+    pub message_id: Option<i64>,
+    pub reason: String,
+    pub details: Option<String>,
+}
+
+/// What a reporter sees about their own report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportSummary {
+    pub id: i64,
+    pub target_type: String,
+    pub reported_user_id: String,
+    pub message_id: Option<i64>,
+    pub reason: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModerationCase {
+    pub id: i64,
+    pub reporter_id: String,
+    pub reported_user_id: String,
+    pub target_type: String,
+    pub message_id: Option<i64>,
+    pub reason: String,
+    pub details: String,
+    /// The reported content as it was when the report was filed
+    pub snapshot: serde_json::Value,
+    pub status: String,
+    pub priority: String,
+    pub assigned_to: Option<String>,
+    pub resolution: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub closed_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModerationCaseEvent {
+    pub id: i64,
+    pub case_id: i64,
+    /// None for events recorded by the system
+    pub actor_id: Option<String>,
+    pub event_type: String,
+    pub details: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModerationCaseDetail {
+    pub case: ModerationCase,
+    pub events: Vec<ModerationCaseEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaseQuery {
+    /// active (default: anything not closed), open, triaged, actioned, closed or all
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TriageCaseRequest {
+    /// low, normal or high
+    pub priority: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaseActionRequest {
+    /// warn, suspend or ban
+    pub action: String,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloseCaseRequest {
+    pub resolution: Option<String>,
+}
+
 // ── Matching trigger ──
 
 #[derive(Debug, Serialize, Deserialize)]
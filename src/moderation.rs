@@ -0,0 +1,326 @@
+use actix_web::{web, HttpResponse};
+use async_trait::async_trait;
+use regex::Regex;
+use std::sync::Arc;
+
+use crate::agent::LlmAgent;
+use crate::db::Database;
+use crate::visibility::known_names_db;
+
+/// Ordered by severity, so the strictest of several verdicts is simply the max
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModerationAction {
+    Allow,
+    Flag,
+    Block,
+}
+
+impl ModerationAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationAction::Allow => "allow",
+            ModerationAction::Flag => "flag",
+            ModerationAction::Block => "block",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationContext {
+    /// A direct message (or edit, or attachment caption) between matched users
+    DirectMessage,
+    /// A message from a user to their own agent
+    AgentChat,
+}
+
+impl ModerationContext {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationContext::DirectMessage => "dm",
+            ModerationContext::AgentChat => "chat",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    pub action: ModerationAction,
+    pub category: String,
+    pub reason: String,
+    /// Which backend produced the verdict ("rules", "llm")
+    pub source: &'static str,
+}
+
+impl Verdict {
+    pub fn allow() -> Self {
+        Verdict {
+            action: ModerationAction::Allow,
+            category: "none".to_string(),
+            reason: String::new(),
+            source: "none",
+        }
+    }
+}
+
+/// Screens user-written text. Handlers only talk to this trait, so backends can be
+/// layered or swapped without touching them. `known_names` are the author's names on
+/// record, for backends that pass the text on to the LLM.
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    async fn review(&self, context: ModerationContext, text: &str, known_names: &[String]) -> Verdict;
+}
+
+// ── Rule-based backend ──
+
+struct Rule {
+    pattern: Regex,
+    action: ModerationAction,
+    category: &'static str,
+    reason: &'static str,
+    /// Contexts the rule applies to; empty means all
+    only_in: &'static [ModerationContext],
+}
+
+/// Regex and wordlist rules. Cheap enough to run on every message.
+pub struct RuleModerator {
+    rules: Vec<Rule>,
+}
+
+impl RuleModerator {
+    pub fn new() -> Self {
+        let rule = |pattern: &str, action, category, reason, only_in| Rule {
+            pattern: Regex::new(pattern).expect("invalid moderation rule"),
+            action,
+            category,
+            reason,
+            only_in,
+        };
+
+        let mut rules = vec![
+            rule(
+                r"(?i)\b(i\s*('?m| am)\s+(going to|gonna)|i\s*('?ll| will)|gonna)\s+(kill|hurt|stab|shoot|rape|beat)\s+(you|u|ya)\b",
+                ModerationAction::Block,
+                "threat",
+                "Threat of violence",
+                &[],
+            ),
+            rule(
+                r"(?i)\b(kill\s+yourself|kys)\b",
+                ModerationAction::Block,
+                "harassment",
+                "Encouraging self-harm",
+                &[],
+            ),
+            rule(
+                r"(?i)\b(bitch|whore|slut|cunt|retard(ed)?|f[a4]gg?[o0]t)\b",
+                ModerationAction::Flag,
+                "harassment",
+                "Abusive language",
+                &[ModerationContext::DirectMessage],
+            ),
+            rule(
+                r"(?i)\b(gift\s?cards?|western\s+union|moneygram|wire\s+(me|the\s+money)|send\s+(me\s+)?(money|cash|\$\s?\d+)|cash\s?app|bitcoin|btc|usdt|crypto(currency)?\s+(investment|opportunity|trading)|investment\s+opportunity)\b",
+                ModerationAction::Flag,
+                "scam",
+                "Possible money request or investment scam",
+                &[ModerationContext::DirectMessage],
+            ),
+            rule(
+                r"(?i)[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}",
+                ModerationAction::Flag,
+                "contact_details",
+                "Shares an email address",
+                &[ModerationContext::DirectMessage],
+            ),
+            rule(
+                r"\+?\(?\d{2,4}\)?[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b",
+                ModerationAction::Flag,
+                "contact_details",
+                "Shares a phone number",
+                &[ModerationContext::DirectMessage],
+            ),
+            rule(
+                r"(?i)\b(whats\s?app|telegram|snapchat|snap|insta(gram)?|signal|kik)\s*(me|:|@|is|handle|id)",
+                ModerationAction::Flag,
+                "contact_details",
+                "Moves the conversation to another app",
+                &[ModerationContext::DirectMessage],
+            ),
+        ];
+
+        // Deployment-specific words that are never allowed, comma-separated
+        let blocklist: Vec<String> = std::env::var("MODERATION_BLOCKLIST")
+            .unwrap_or_default()
+            .split(',')
+            .map(|w| regex::escape(w.trim()))
+            .filter(|w| !w.is_empty())
+            .collect();
+        if !blocklist.is_empty() {
+            rules.push(rule(
+                &format!(r"(?i)\b({})\b", blocklist.join("|")),
+                ModerationAction::Block,
+                "blocklist",
+                "Contains a blocked term",
+                &[],
+            ));
+        }
+
+        RuleModerator { rules }
+    }
+}
+
+#[async_trait]
+impl Moderator for RuleModerator {
+    async fn review(&self, context: ModerationContext, text: &str, _known_names: &[String]) -> Verdict {
+        self.rules
+            .iter()
+            .filter(|r| r.only_in.is_empty() || r.only_in.contains(&context))
+            .filter(|r| r.pattern.is_match(text))
+            .max_by_key(|r| r.action)
+            .map(|r| Verdict {
+                action: r.action,
+                category: r.category.to_string(),
+                reason: r.reason.to_string(),
+                source: "rules",
+            })
+            .unwrap_or_else(Verdict::allow)
+    }
+}
+
+// ── LLM backend ──
+
+/// Asks the LLM to classify the message. Fails open: if the LLM is unavailable the
+/// message is allowed and the rule-based layer is all that applies.
+pub struct LlmModerator {
+    agent: Arc<LlmAgent>,
+}
+
+impl LlmModerator {
+    pub fn new(agent: Arc<LlmAgent>) -> Self {
+        LlmModerator { agent }
+    }
+}
+
+#[async_trait]
+impl Moderator for LlmModerator {
+    async fn review(&self, context: ModerationContext, text: &str, known_names: &[String]) -> Verdict {
+        let label = match context {
+            ModerationContext::DirectMessage => "direct message to a match",
+            ModerationContext::AgentChat => "message to the user's own AI assistant",
+        };
+        match self.agent.classify_message(label, text, known_names).await {
+            Ok((verdict, category, reason)) => Verdict {
+                action: match verdict.as_str() {
+                    "block" => ModerationAction::Block,
+                    "flag" => ModerationAction::Flag,
+                    _ => ModerationAction::Allow,
+                },
+                category,
+                reason,
+                source: "llm",
+            },
+            Err(e) => {
+                log::warn!("LLM moderation unavailable, allowing message: {}", e);
+                Verdict::allow()
+            }
+        }
+    }
+}
+
+// ── Layering ──
+
+/// Runs each backend in order and keeps the strictest verdict, stopping early on a block
+pub struct LayeredModerator {
+    layers: Vec<Box<dyn Moderator>>,
+}
+
+#[async_trait]
+impl Moderator for LayeredModerator {
+    async fn review(&self, context: ModerationContext, text: &str, known_names: &[String]) -> Verdict {
+        let mut strictest = Verdict::allow();
+        for layer in &self.layers {
+            let verdict = layer.review(context, text, known_names).await;
+            if verdict.action > strictest.action {
+                strictest = verdict;
+            }
+            if strictest.action == ModerationAction::Block {
+                break;
+            }
+        }
+        strictest
+    }
+}
+
+/// Rules always run; `MODERATION_LLM=true` adds the LLM classifier behind them
+pub fn moderator_from_env(agent: Arc<LlmAgent>) -> Arc<dyn Moderator> {
+    let mut layers: Vec<Box<dyn Moderator>> = vec![Box::new(RuleModerator::new())];
+    if std::env::var("MODERATION_LLM").map(|v| v == "true" || v == "1").unwrap_or(false) {
+        layers.push(Box::new(LlmModerator::new(agent)));
+    }
+    Arc::new(LayeredModerator { layers })
+}
+
+// ── Handler helpers ──
+
+/// Review `text` before it is stored. Blocked attempts are recorded for admins and turned
+/// into an error response; anything else is returned so the caller can record a flag
+/// once it knows the stored message's id.
+pub async fn screen(
+    moderator: &dyn Moderator,
+    db: &web::Data<Database>,
+    user_id: &str,
+    context: ModerationContext,
+    match_id: Option<i64>,
+    text: &str,
+) -> Result<Verdict, HttpResponse> {
+    let known_names = {
+        let conn = db.conn.lock().unwrap();
+        known_names_db(&conn, user_id)
+    };
+    let verdict = moderator.review(context, text, &known_names).await;
+    if verdict.action != ModerationAction::Block {
+        return Ok(verdict);
+    }
+
+    {
+        let conn = db.conn.lock().unwrap();
+        record_flag(&conn, user_id, context, match_id, None, text, &verdict);
+    }
+    log::info!("🚫 Blocked {} message from {} ({})", context.as_str(), user_id, verdict.category);
+
+    Err(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+        "error": format!("Message not sent: {}", verdict.reason),
+        "category": verdict.category,
+    })))
+}
+
+/// Queue a flagged (or blocked) message for admin review. Allowed verdicts are ignored.
+pub fn record_flag(
+    conn: &rusqlite::Connection,
+    user_id: &str,
+    context: ModerationContext,
+    match_id: Option<i64>,
+    message_id: Option<i64>,
+    content: &str,
+    verdict: &Verdict,
+) {
+    if verdict.action == ModerationAction::Allow {
+        return;
+    }
+    if let Err(e) = conn.execute(
+        "INSERT INTO moderation_flags (user_id, context, match_id, message_id, content, action, category, reason, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        rusqlite::params![
+            user_id,
+            context.as_str(),
+            match_id,
+            message_id,
+            content,
+            verdict.action.as_str(),
+            &verdict.category,
+            &verdict.reason,
+            verdict.source,
+        ],
+    ) {
+        log::error!("Failed to record moderation flag for {}: {}", user_id, e);
+    }
+}
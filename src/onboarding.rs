@@ -0,0 +1,657 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Datelike;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+use crate::profile_history::ProfileChange;
+use crate::routes::{get_agent_profile_db, save_agent_profile_db};
+use crate::visibility::{profile_field, profile_field_mut, PROFILE_FIELDS};
+
+/// Bump when a question changes meaning, so answers given to the old wording can be told apart
+const QUESTION_SET_VERSION: i64 = 1;
+const MAX_TEXT_CHARS: usize = 1000;
+
+const GENDERS: &[(&str, &str)] = &[("woman", "Woman"), ("man", "Man"), ("non_binary", "Non-binary")];
+
+fn current_year() -> i64 {
+    chrono::Utc::now().year() as i64
+}
+
+fn options(pairs: &[(&str, &str)]) -> Vec<OnboardingOption> {
+    pairs
+        .iter()
+        .map(|(value, label)| OnboardingOption { value: value.to_string(), label: label.to_string() })
+        .collect()
+}
+
+fn question(id: &str, prompt: &str, kind: &str) -> OnboardingQuestion {
+    OnboardingQuestion {
+        id: id.to_string(),
+        prompt: prompt.to_string(),
+        kind: kind.to_string(),
+        options: Vec::new(),
+        required: false,
+        min: None,
+        max: None,
+        profile_field: None,
+        hard_filter: false,
+    }
+}
+
+/// The profile field an answer seeds; must be one of `PROFILE_FIELDS`, or the answer would be
+/// silently dropped when the profile is seeded
+fn seeds_field(field: &str) -> Option<String> {
+    debug_assert!(PROFILE_FIELDS.contains(&field), "unknown profile field {}", field);
+    Some(field.to_string())
+}
+
+pub fn sections() -> Vec<OnboardingSection> {
+    let year = current_year();
+    vec![
+        OnboardingSection {
+            id: "about_you".to_string(),
+            title: "About you".to_string(),
+            questions: vec![
+                OnboardingQuestion {
+                    required: true,
+                    hard_filter: true,
+                    min: Some(year - 100),
+                    max: Some(year - 18),
+                    ..question("birth_year", "What year were you born?", "number")
+                },
+                OnboardingQuestion {
+                    options: options(GENDERS),
+                    required: true,
+                    hard_filter: true,
+                    profile_field: seeds_field("raw_notes"),
+                    ..question("gender", "How do you identify?", "choice")
+                },
+                OnboardingQuestion {
+                    required: true,
+                    hard_filter: true,
+                    profile_field: seeds_field("raw_notes"),
+                    ..question("city", "Which city do you live in?", "text")
+                },
+                OnboardingQuestion {
+                    profile_field: seeds_field("personality_summary"),
+                    ..question("personality", "How would your closest friends describe you?", "text")
+                },
+                OnboardingQuestion {
+                    options: options(&[
+                        ("direct", "Direct and to the point"),
+                        ("chatty", "Warm and chatty"),
+                        ("thoughtful", "Thoughtful, I take my time"),
+                        ("playful", "Playful and teasing"),
+                    ]),
+                    profile_field: seeds_field("communication_style"),
+                    ..question("communication_style", "How do you usually talk with people you like?", "choice")
+                },
+            ],
+        },
+        OnboardingSection {
+            id: "interests".to_string(),
+            title: "Interests".to_string(),
+            questions: vec![
+                OnboardingQuestion {
+                    options: options(&[
+                        ("outdoors", "Hiking and the outdoors"),
+                        ("travel", "Travel"),
+                        ("music", "Music and gigs"),
+                        ("cooking", "Cooking and food"),
+                        ("reading", "Reading"),
+                        ("fitness", "Sport and fitness"),
+                        ("gaming", "Gaming"),
+                        ("art", "Art and design"),
+                        ("film", "Film and TV"),
+                        ("tech", "Technology"),
+                        ("animals", "Animals"),
+                        ("volunteering", "Volunteering"),
+                    ]),
+                    profile_field: seeds_field("interests"),
+                    ..question("interests", "What do you love spending time on?", "multi_choice")
+                },
+                OnboardingQuestion {
+                    profile_field: seeds_field("interests"),
+                    ..question("other_interests", "Anything else you're into?", "text")
+                },
+            ],
+        },
+        OnboardingSection {
+            id: "values".to_string(),
+            title: "Values".to_string(),
+            questions: vec![OnboardingQuestion {
+                options: options(&[
+                    ("family", "Family"),
+                    ("honesty", "Honesty"),
+                    ("ambition", "Ambition"),
+                    ("adventure", "Adventure"),
+                    ("faith", "Faith"),
+                    ("humor", "Humor"),
+                    ("kindness", "Kindness"),
+                    ("independence", "Independence"),
+                    ("growth", "Personal growth"),
+                ]),
+                profile_field: seeds_field("core_values"),
+                ..question("core_values", "Which of these matter most to you?", "multi_choice")
+            }],
+        },
+        OnboardingSection {
+            id: "partner".to_string(),
+            title: "Who you're looking for".to_string(),
+            questions: vec![
+                OnboardingQuestion {
+                    options: options(GENDERS),
+                    required: true,
+                    hard_filter: true,
+                    profile_field: seeds_field("looking_for"),
+                    ..question("seeking_genders", "Who would you like to meet?", "multi_choice")
+                },
+                OnboardingQuestion {
+                    min: Some(18),
+                    max: Some(100),
+                    hard_filter: true,
+                    ..question("age_min", "Youngest age you'd date", "number")
+                },
+                OnboardingQuestion {
+                    min: Some(18),
+                    max: Some(100),
+                    hard_filter: true,
+                    ..question("age_max", "Oldest age you'd date", "number")
+                },
+                OnboardingQuestion {
+                    hard_filter: true,
+                    ..question("same_city_only", "Only match with people in your city?", "boolean")
+                },
+                OnboardingQuestion {
+                    options: options(&[
+                        ("long_term", "A long-term relationship"),
+                        ("short_term", "Something casual"),
+                        ("friendship_first", "Friendship first, then see"),
+                        ("not_sure", "Not sure yet"),
+                    ]),
+                    profile_field: seeds_field("looking_for"),
+                    ..question("relationship_goal", "What are you hoping to find?", "choice")
+                },
+                OnboardingQuestion {
+                    profile_field: seeds_field("looking_for"),
+                    ..question("partner_qualities", "Describe the kind of person you'd click with", "text")
+                },
+                OnboardingQuestion {
+                    options: options(&[
+                        ("smoking", "Smoking"),
+                        ("heavy_drinking", "Heavy drinking"),
+                        ("wants_kids", "Wants children"),
+                        ("no_kids", "Doesn't want children"),
+                        ("long_distance", "Long distance"),
+                    ]),
+                    profile_field: seeds_field("deal_breakers"),
+                    ..question("deal_breakers", "Any of these a deal breaker?", "multi_choice")
+                },
+                OnboardingQuestion {
+                    profile_field: seeds_field("deal_breakers"),
+                    ..question("other_deal_breakers", "Anything else that's a no for you?", "text")
+                },
+            ],
+        },
+    ]
+}
+
+fn find_question<'a>(sections: &'a [OnboardingSection], id: &str) -> Option<&'a OnboardingQuestion> {
+    sections.iter().flat_map(|s| &s.questions).find(|q| q.id == id)
+}
+
+fn option_label<'a>(question: &'a OnboardingQuestion, value: &str) -> Option<&'a str> {
+    question.options.iter().find(|o| o.value == value).map(|o| o.label.as_str())
+}
+
+/// Check an answer against its question. `Ok(None)` clears the answer.
+fn normalize_answer(question: &OnboardingQuestion, value: &Value) -> Result<Option<Value>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    match question.kind.as_str() {
+        "text" => {
+            let text = value.as_str().ok_or("must be a string")?.trim();
+            if text.chars().count() > MAX_TEXT_CHARS {
+                return Err(format!("must be at most {} characters", MAX_TEXT_CHARS));
+            }
+            Ok((!text.is_empty()).then(|| Value::from(text)))
+        }
+        "number" => {
+            let n = value.as_i64().ok_or("must be a whole number")?;
+            if question.min.is_some_and(|min| n < min) || question.max.is_some_and(|max| n > max) {
+                return Err(format!("must be between {} and {}", question.min.unwrap_or(i64::MIN), question.max.unwrap_or(i64::MAX)));
+            }
+            Ok(Some(Value::from(n)))
+        }
+        "boolean" => Ok(Some(Value::from(value.as_bool().ok_or("must be true or false")?))),
+        "choice" => {
+            let choice = value.as_str().ok_or("must be a string")?;
+            if choice.is_empty() {
+                return Ok(None);
+            }
+            option_label(question, choice).ok_or("is not one of the options")?;
+            Ok(Some(Value::from(choice)))
+        }
+        "multi_choice" => {
+            let mut chosen: Vec<&str> = Vec::new();
+            for item in value.as_array().ok_or("must be a list")? {
+                let choice = item.as_str().ok_or("must be a list of strings")?;
+                option_label(question, choice).ok_or_else(|| format!("{} is not one of the options", choice))?;
+                if !chosen.contains(&choice) {
+                    chosen.push(choice);
+                }
+            }
+            Ok((!chosen.is_empty()).then(|| Value::from(chosen)))
+        }
+        _ => Err("has an unknown question type".to_string()),
+    }
+}
+
+pub fn answers_db(conn: &rusqlite::Connection, user_id: &str) -> BTreeMap<String, Value> {
+    let mut stmt = conn
+        .prepare("SELECT question_id, answer FROM onboarding_answers WHERE user_id = ?1")
+        .unwrap();
+    stmt.query_map(rusqlite::params![user_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .filter_map(|(id, answer)| serde_json::from_str(&answer).ok().map(|v| (id, v)))
+        .collect()
+}
+
+pub fn get_preferences_db(conn: &rusqlite::Connection, user_id: &str) -> MatchPreferences {
+    conn.query_row(
+        "SELECT birth_year, gender, city, seeking_genders, age_min, age_max, same_city_only FROM match_preferences WHERE user_id = ?1",
+        rusqlite::params![user_id],
+        |row| {
+            let seeking: String = row.get(3)?;
+            Ok(MatchPreferences {
+                birth_year: row.get(0)?,
+                gender: row.get(1)?,
+                city: row.get(2)?,
+                seeking_genders: seeking.split(',').filter(|g| !g.is_empty()).map(String::from).collect(),
+                age_min: row.get(4)?,
+                age_max: row.get(5)?,
+                same_city_only: row.get::<_, i32>(6)? != 0,
+            })
+        },
+    )
+    .unwrap_or_default()
+}
+
+fn preferences_from(answers: &BTreeMap<String, Value>) -> MatchPreferences {
+    let text = |id: &str| answers.get(id).and_then(|v| v.as_str()).map(String::from);
+    MatchPreferences {
+        birth_year: answers.get("birth_year").and_then(|v| v.as_i64()),
+        gender: text("gender"),
+        city: text("city"),
+        seeking_genders: answers
+            .get("seeking_genders")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|g| g.as_str().map(String::from)).collect())
+            .unwrap_or_default(),
+        age_min: answers.get("age_min").and_then(|v| v.as_i64()),
+        age_max: answers.get("age_max").and_then(|v| v.as_i64()),
+        same_city_only: answers.get("same_city_only").and_then(|v| v.as_bool()).unwrap_or(false),
+    }
+}
+
+/// Whether `them` passes every hard filter `me` has set. A filter on something `them` never
+/// answered excludes them: a hard filter is a promise not to show people outside it.
+fn accepts(me: &MatchPreferences, them: &MatchPreferences, year: i64) -> bool {
+    if !me.seeking_genders.is_empty() && !them.gender.as_ref().is_some_and(|g| me.seeking_genders.contains(g)) {
+        return false;
+    }
+    if me.age_min.is_some() || me.age_max.is_some() {
+        let Some(age) = them.birth_year.map(|b| year - b) else {
+            return false;
+        };
+        if me.age_min.is_some_and(|min| age < min) || me.age_max.is_some_and(|max| age > max) {
+            return false;
+        }
+    }
+    if me.same_city_only {
+        let same = match (&me.city, &them.city) {
+            (Some(a), Some(b)) => a.trim().eq_ignore_ascii_case(b.trim()),
+            _ => false,
+        };
+        if !same {
+            return false;
+        }
+    }
+    true
+}
+
+/// Both users pass each other's hard filters
+pub fn mutually_acceptable(a: &MatchPreferences, b: &MatchPreferences) -> bool {
+    let year = current_year();
+    accepts(a, b, year) && accepts(b, a, year)
+}
+
+/// Profile field text built from the answers, one line per answered question
+fn profile_seeds(sections: &[OnboardingSection], answers: &BTreeMap<String, Value>) -> BTreeMap<String, String> {
+    let mut seeds: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for question in sections.iter().flat_map(|s| &s.questions) {
+        let (Some(field), Some(answer)) = (&question.profile_field, answers.get(&question.id)) else {
+            continue;
+        };
+        let text = match answer {
+            Value::String(s) => option_label(question, s).unwrap_or(s).to_string(),
+            Value::Array(items) => items
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|v| option_label(question, v).unwrap_or(v))
+                .collect::<Vec<_>>()
+                .join(", "),
+            other => other.to_string(),
+        };
+        let line = match question.id.as_str() {
+            "gender" => format!("Gender: {}", text),
+            "city" => format!("Lives in {}", text),
+            "seeking_genders" => format!("Interested in: {}", text),
+            "relationship_goal" => format!("Hoping to find: {}", text),
+            _ => text,
+        };
+        seeds.entry(field.clone()).or_default().push(line);
+    }
+    seeds.into_iter().map(|(field, lines)| (field, lines.join("\n"))).collect()
+}
+
+/// Hard filters in words for the chat prompt, e.g. "women, aged 28-38, in Berlin"
+fn describe_preferences(prefs: &MatchPreferences) -> String {
+    let mut parts = Vec::new();
+    if !prefs.seeking_genders.is_empty() {
+        let genders: Vec<&str> = prefs
+            .seeking_genders
+            .iter()
+            .map(|g| match g.as_str() {
+                "woman" => "women",
+                "man" => "men",
+                _ => "non-binary people",
+            })
+            .collect();
+        parts.push(genders.join(" or "));
+    }
+    match (prefs.age_min, prefs.age_max) {
+        (Some(min), Some(max)) => parts.push(format!("aged {}-{}", min, max)),
+        (Some(min), None) => parts.push(format!("aged {} or older", min)),
+        (None, Some(max)) => parts.push(format!("aged {} or younger", max)),
+        (None, None) => {}
+    }
+    if prefs.same_city_only && let Some(city) = &prefs.city {
+        parts.push(format!("in {}", city));
+    }
+    parts.join(", ")
+}
+
+/// What the chat agent already knows from onboarding, so it doesn't ask again
+pub fn chat_context(conn: &rusqlite::Connection, user_id: &str) -> OnboardingContext {
+    let answers = answers_db(conn, user_id);
+    OnboardingContext {
+        topics: sections()
+            .into_iter()
+            .filter(|s| s.questions.iter().any(|q| answers.contains_key(&q.id)))
+            .map(|s| s.title)
+            .collect(),
+        preferences: describe_preferences(&get_preferences_db(conn, user_id)),
+    }
+}
+
+fn onboarding_state(conn: &rusqlite::Connection, user_id: &str) -> OnboardingState {
+    OnboardingState {
+        question_set_version: QUESTION_SET_VERSION,
+        sections: sections(),
+        answers: answers_db(conn, user_id),
+        completed_at: conn
+            .query_row("SELECT onboarding_completed_at FROM users WHERE id = ?1", rusqlite::params![user_id], |row| row.get(0))
+            .unwrap_or(None),
+        preferences: get_preferences_db(conn, user_id),
+    }
+}
+
+// ── Handlers ──
+
+pub async fn get_onboarding(
+    req: HttpRequest,
+    db: web::Data<Database>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let conn = db.conn.lock().unwrap();
+    HttpResponse::Ok().json(onboarding_state(&conn, &claims.sub))
+}
+
+pub async fn submit_onboarding(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    body: web::Json<SubmitOnboardingRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let sections = sections();
+    let mut changes: HashMap<String, Option<Value>> = HashMap::new();
+    for (id, value) in &body.answers {
+        let Some(question) = find_question(&sections, id) else {
+            return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Unknown question: {}", id)}));
+        };
+        match normalize_answer(question, value) {
+            Ok(answer) => {
+                changes.insert(id.clone(), answer);
+            }
+            Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("{} {}", id, e)})),
+        }
+    }
+
+    let conn = db.conn.lock().unwrap();
+    let previous = answers_db(&conn, &claims.sub);
+    let mut answers = previous.clone();
+    for (id, answer) in &changes {
+        match answer {
+            Some(v) => answers.insert(id.clone(), v.clone()),
+            None => answers.remove(id),
+        };
+    }
+    let prefs = preferences_from(&answers);
+    if let (Some(min), Some(max)) = (prefs.age_min, prefs.age_max)
+        && min > max
+    {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "age_min must not be above age_max"}));
+    }
+
+    for (id, answer) in &changes {
+        match answer {
+            Some(v) => conn.execute(
+                "INSERT INTO onboarding_answers (user_id, question_id, answer, question_set_version) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(user_id, question_id) DO UPDATE SET answer = ?3, question_set_version = ?4, answered_at = datetime('now')",
+                rusqlite::params![&claims.sub, id, v.to_string(), QUESTION_SET_VERSION],
+            ),
+            None => conn.execute(
+                "DELETE FROM onboarding_answers WHERE user_id = ?1 AND question_id = ?2",
+                rusqlite::params![&claims.sub, id],
+            ),
+        }
+        .unwrap();
+    }
+
+    conn.execute(
+        "INSERT INTO match_preferences (user_id, birth_year, gender, city, seeking_genders, age_min, age_max, same_city_only) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(user_id) DO UPDATE SET birth_year = ?2, gender = ?3, city = ?4, seeking_genders = ?5, age_min = ?6, age_max = ?7, same_city_only = ?8, updated_at = datetime('now')",
+        rusqlite::params![
+            &claims.sub,
+            prefs.birth_year,
+            &prefs.gender,
+            &prefs.city,
+            prefs.seeking_genders.join(","),
+            prefs.age_min,
+            prefs.age_max,
+            prefs.same_city_only as i32,
+        ],
+    ).unwrap();
+
+    let complete = sections
+        .iter()
+        .flat_map(|s| &s.questions)
+        .all(|q| !q.required || answers.contains_key(&q.id));
+    conn.execute(
+        "UPDATE users SET onboarding_completed_at = CASE WHEN ?1 THEN COALESCE(onboarding_completed_at, datetime('now')) END WHERE id = ?2",
+        rusqlite::params![complete, &claims.sub],
+    ).unwrap();
+
+    // A field is only rewritten while it still holds what the previous answers produced;
+    // anything the user or the agent has changed since is theirs to keep
+    let before = profile_seeds(&sections, &previous);
+    let after = profile_seeds(&sections, &answers);
+    let mut profile = get_agent_profile_db(&conn, &claims.sub);
+    let mut seeded_fields = Vec::new();
+    let mut kept_fields = Vec::new();
+    for field in PROFILE_FIELDS {
+        let old_seed = before.get(*field).map(String::as_str).unwrap_or_default();
+        let new_seed = after.get(*field).map(String::as_str).unwrap_or_default();
+        if old_seed == new_seed {
+            continue;
+        }
+        if profile_field(&profile, field) != old_seed {
+            kept_fields.push(field.to_string());
+            continue;
+        }
+        if let Some(slot) = profile_field_mut(&mut profile, field) {
+            *slot = new_seed.to_string();
+            seeded_fields.push(field.to_string());
+        }
+    }
+    if let Err(e) = save_agent_profile_db(&conn, &claims.sub, &profile, ProfileChange::User) {
+        log::error!("Failed to seed profile from onboarding for {}: {}", claims.sub, e);
+        return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to update profile"}));
+    }
+
+    HttpResponse::Ok().json(OnboardingResult {
+        state: onboarding_state(&conn, &claims.sub),
+        seeded_fields,
+        kept_fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn q(id: &str) -> OnboardingQuestion {
+        find_question(&sections(), id).unwrap().clone()
+    }
+
+    fn person(gender: &str, birth_year: i64, city: &str) -> MatchPreferences {
+        MatchPreferences {
+            gender: Some(gender.to_string()),
+            birth_year: Some(birth_year),
+            city: Some(city.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn seeded_fields_are_profile_fields() {
+        for question in sections().iter().flat_map(|s| &s.questions) {
+            if let Some(field) = &question.profile_field {
+                assert!(PROFILE_FIELDS.contains(&field.as_str()), "{} seeds unknown field {}", question.id, field);
+            }
+        }
+    }
+
+    #[test]
+    fn normalizes_text() {
+        let question = q("city");
+        assert_eq!(normalize_answer(&question, &json!("  Berlin ")), Ok(Some(json!("Berlin"))));
+        assert_eq!(normalize_answer(&question, &json!("   ")), Ok(None));
+        assert_eq!(normalize_answer(&question, &Value::Null), Ok(None));
+        assert!(normalize_answer(&question, &json!(5)).is_err());
+        assert!(normalize_answer(&question, &json!("x".repeat(MAX_TEXT_CHARS + 1))).is_err());
+    }
+
+    #[test]
+    fn checks_number_range() {
+        let question = q("age_min");
+        assert_eq!(normalize_answer(&question, &json!(30)), Ok(Some(json!(30))));
+        assert!(normalize_answer(&question, &json!(17)).is_err());
+        assert!(normalize_answer(&question, &json!(101)).is_err());
+        assert!(normalize_answer(&question, &json!(30.5)).is_err());
+        assert!(normalize_answer(&question, &json!("30")).is_err());
+    }
+
+    #[test]
+    fn checks_choices() {
+        let question = q("gender");
+        assert_eq!(normalize_answer(&question, &json!("woman")), Ok(Some(json!("woman"))));
+        assert_eq!(normalize_answer(&question, &json!("")), Ok(None));
+        assert!(normalize_answer(&question, &json!("Woman")).is_err());
+
+        let multi = q("seeking_genders");
+        assert_eq!(normalize_answer(&multi, &json!(["man", "woman", "man"])), Ok(Some(json!(["man", "woman"]))));
+        assert_eq!(normalize_answer(&multi, &json!([])), Ok(None));
+        assert!(normalize_answer(&multi, &json!(["robot"])).is_err());
+        assert!(normalize_answer(&multi, &json!("man")).is_err());
+    }
+
+    #[test]
+    fn checks_booleans() {
+        let question = q("same_city_only");
+        assert_eq!(normalize_answer(&question, &json!(true)), Ok(Some(json!(true))));
+        assert!(normalize_answer(&question, &json!("yes")).is_err());
+    }
+
+    #[test]
+    fn no_filters_accept_anyone() {
+        let me = MatchPreferences::default();
+        assert!(accepts(&me, &MatchPreferences::default(), 2026));
+        assert!(accepts(&me, &person("man", 1990, "Paris"), 2026));
+    }
+
+    #[test]
+    fn filters_by_gender_and_age() {
+        let me = MatchPreferences {
+            seeking_genders: vec!["man".to_string()],
+            age_min: Some(30),
+            age_max: Some(40),
+            ..Default::default()
+        };
+        assert!(accepts(&me, &person("man", 1990, "Berlin"), 2026));
+        assert!(accepts(&me, &person("man", 1986, "Berlin"), 2026));
+        assert!(!accepts(&me, &person("man", 1985, "Berlin"), 2026));
+        assert!(!accepts(&me, &person("man", 1997, "Berlin"), 2026));
+        assert!(!accepts(&me, &person("woman", 1990, "Berlin"), 2026));
+    }
+
+    #[test]
+    fn a_filter_excludes_unanswered_questions() {
+        let seeking = MatchPreferences { seeking_genders: vec!["woman".to_string()], ..Default::default() };
+        assert!(!accepts(&seeking, &MatchPreferences::default(), 2026));
+
+        let aged = MatchPreferences { age_min: Some(25), ..Default::default() };
+        assert!(!accepts(&aged, &MatchPreferences::default(), 2026));
+    }
+
+    #[test]
+    fn same_city_ignores_case_and_spacing() {
+        let me = MatchPreferences { same_city_only: true, ..person("woman", 1990, "Berlin") };
+        assert!(accepts(&me, &person("man", 1990, "  berlin "), 2026));
+        assert!(!accepts(&me, &person("man", 1990, "Paris"), 2026));
+        assert!(!accepts(&me, &MatchPreferences::default(), 2026));
+    }
+
+    #[test]
+    fn both_sides_must_accept() {
+        let her = MatchPreferences { seeking_genders: vec!["man".to_string()], ..person("woman", 1990, "Berlin") };
+        let him = MatchPreferences { seeking_genders: vec!["man".to_string()], ..person("man", 1990, "Berlin") };
+        assert!(accepts(&her, &him, 2026));
+        assert!(!mutually_acceptable(&her, &him));
+    }
+}
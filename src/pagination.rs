@@ -0,0 +1,148 @@
+use actix_web::HttpResponse;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use crate::models::{Page, PageQuery};
+
+const CURSOR_PREFIX: &str = "c1:";
+
+/// Cursors are opaque to clients; today they wrap a row id
+pub fn encode_cursor(id: i64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}{}", CURSOR_PREFIX, id))
+}
+
+pub fn decode_cursor(cursor: &str) -> Option<i64> {
+    let raw = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    let raw = String::from_utf8(raw).ok()?;
+    raw.strip_prefix(CURSOR_PREFIX)?.parse().ok()
+}
+
+/// Which slice of a list to read. `Older` walks back from the newest row (or from a cursor),
+/// `Newer` walks forward from a cursor.
+pub enum PageDirection {
+    Older(Option<i64>),
+    Newer(i64),
+}
+
+pub struct PageRequest {
+    pub direction: PageDirection,
+    pub limit: i64,
+}
+
+impl PageRequest {
+    pub fn from_query(query: &PageQuery, default_limit: i64, max_limit: i64) -> Result<Self, HttpResponse> {
+        let invalid = || HttpResponse::BadRequest().json(serde_json::json!({"error": "Invalid cursor"}));
+
+        let direction = match (&query.before, &query.after) {
+            (Some(_), Some(_)) => {
+                return Err(HttpResponse::BadRequest().json(serde_json::json!({"error": "Use either 'before' or 'after', not both"})));
+            }
+            (Some(before), None) => PageDirection::Older(Some(decode_cursor(before).ok_or_else(invalid)?)),
+            (None, Some(after)) => PageDirection::Newer(decode_cursor(after).ok_or_else(invalid)?),
+            (None, None) => PageDirection::Older(None),
+        };
+
+        Ok(PageRequest {
+            direction,
+            limit: query.limit.unwrap_or(default_limit).clamp(1, max_limit),
+        })
+    }
+
+    /// SQL fragment bounding `column` and ordering the scan; bind `bound()` and `fetch_limit()` to it
+    pub fn clause(&self, column: &str) -> String {
+        match self.direction {
+            PageDirection::Older(_) => format!("{} < ? ORDER BY {} DESC LIMIT ?", column, column),
+            PageDirection::Newer(_) => format!("{} > ? ORDER BY {} ASC LIMIT ?", column, column),
+        }
+    }
+
+    pub fn bound(&self) -> i64 {
+        match self.direction {
+            PageDirection::Older(before) => before.unwrap_or(i64::MAX),
+            PageDirection::Newer(after) => after,
+        }
+    }
+
+    /// One extra row tells us whether another page exists
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// Turn rows fetched with `clause()` into a page. `chronological` lists oldest first
+    /// (chat threads); otherwise newest first (feeds).
+    pub fn paginate<T>(&self, mut rows: Vec<T>, id_of: impl Fn(&T) -> i64, chronological: bool) -> Page<T> {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+
+        let next_cursor = if has_more { rows.last().map(|r| encode_cursor(id_of(r))) } else { None };
+        let prev_cursor = rows.first().map(|r| encode_cursor(id_of(r)));
+
+        // Rows arrive newest-first when walking back and oldest-first when walking forward
+        let newest_first = matches!(self.direction, PageDirection::Older(_));
+        if newest_first == chronological {
+            rows.reverse();
+        }
+
+        Page {
+            items: rows,
+            next_cursor,
+            prev_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        for id in [0, 1, 42, i64::MAX] {
+            assert_eq!(decode_cursor(&encode_cursor(id)), Some(id));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        assert_eq!(decode_cursor(""), None);
+        assert_eq!(decode_cursor("not base64!"), None);
+        assert_eq!(decode_cursor(&URL_SAFE_NO_PAD.encode("42")), None);
+        assert_eq!(decode_cursor(&URL_SAFE_NO_PAD.encode("c2:42")), None);
+        assert_eq!(decode_cursor(&URL_SAFE_NO_PAD.encode("c1:forty")), None);
+        assert_eq!(decode_cursor(&URL_SAFE_NO_PAD.encode([0xff, 0xfe])), None);
+    }
+
+    #[test]
+    fn paginates_newest_first_and_chronologically() {
+        let query = PageQuery { before: None, after: None, limit: Some(2) };
+        let page = PageRequest::from_query(&query, 50, 200).unwrap();
+        let rows = vec![5, 4, 3];
+
+        let feed = page.paginate(rows.clone(), |r| *r, false);
+        assert_eq!(feed.items, vec![5, 4]);
+        assert_eq!(feed.next_cursor.as_deref().and_then(decode_cursor), Some(4));
+
+        let thread = page.paginate(rows, |r| *r, true);
+        assert_eq!(thread.items, vec![4, 5]);
+    }
+
+    #[test]
+    fn last_page_has_no_next_cursor() {
+        let query = PageQuery { before: None, after: None, limit: Some(5) };
+        let page = PageRequest::from_query(&query, 50, 200).unwrap();
+        assert_eq!(page.paginate(vec![3, 2, 1], |r| *r, false).next_cursor, None);
+    }
+
+    #[test]
+    fn clamps_limit_and_rejects_both_directions() {
+        let query = PageQuery { before: None, after: None, limit: Some(10_000) };
+        assert_eq!(PageRequest::from_query(&query, 50, 200).unwrap().limit, 200);
+
+        let cursor = encode_cursor(1);
+        let both = PageQuery { before: Some(cursor.clone()), after: Some(cursor), limit: None };
+        assert!(PageRequest::from_query(&both, 50, 200).is_err());
+
+        let bad = PageQuery { before: Some("nope".to_string()), after: None, limit: None };
+        assert!(PageRequest::from_query(&bad, 50, 200).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use actix_web::web;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+
+/// Result of checking a password against a stored PHC hash
+pub struct PasswordCheck {
+    pub valid: bool,
+    /// The stored hash uses a legacy algorithm or outdated parameters
+    pub needs_rehash: bool,
+}
+
+/// Argon2id parameters, configurable through `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS`
+/// and `ARGON2_PARALLELISM`. Defaults follow the OWASP baseline (19 MiB, 2 passes, 1 lane).
+fn argon2_params() -> Params {
+    let env_u32 = |key: &str, default: u32| {
+        std::env::var(key)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+
+    let memory = env_u32("ARGON2_MEMORY_KIB", 19 * 1024);
+    let iterations = env_u32("ARGON2_ITERATIONS", 2);
+    let parallelism = env_u32("ARGON2_PARALLELISM", 1);
+
+    Params::new(memory, iterations, parallelism, None).unwrap_or_else(|e| {
+        log::warn!("Invalid Argon2 parameters ({}), falling back to defaults", e);
+        Params::default()
+    })
+}
+
+fn argon2() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params())
+}
+
+/// Hash a password with argon2id. The algorithm and parameters are recorded in the PHC string.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut argon2::password_hash::rand_core::OsRng);
+    argon2()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+/// Verify a password against either an argon2 PHC string or a legacy bcrypt hash
+pub fn verify_password(password: &str, stored: &str) -> PasswordCheck {
+    if stored.starts_with("$2") {
+        return PasswordCheck {
+            valid: bcrypt::verify(password, stored).unwrap_or(false),
+            needs_rehash: true,
+        };
+    }
+
+    let parsed = match PasswordHash::new(stored) {
+        Ok(h) => h,
+        Err(_) => return PasswordCheck { valid: false, needs_rehash: false },
+    };
+
+    let valid = argon2().verify_password(password.as_bytes(), &parsed).is_ok();
+    let current = argon2_params();
+    let needs_rehash = parsed.algorithm != Algorithm::Argon2id.ident()
+        || Params::try_from(&parsed).map_or(true, |p| {
+            p.m_cost() != current.m_cost() || p.t_cost() != current.t_cost() || p.p_cost() != current.p_cost()
+        });
+
+    PasswordCheck { valid, needs_rehash }
+}
+
+/// `hash_password` on the blocking thread pool. Argon2 is slow on purpose, so it must not run
+/// on an async worker, let alone while the database lock is held.
+pub async fn hash_password_async(password: String) -> Result<String, String> {
+    web::block(move || hash_password(&password))
+        .await
+        .map_err(|e| format!("Failed to hash password: {}", e))?
+}
+
+/// `verify_password` on the blocking thread pool; a pool failure counts as a wrong password
+pub async fn verify_password_async(password: String, stored: String) -> PasswordCheck {
+    web::block(move || verify_password(&password, &stored))
+        .await
+        .unwrap_or(PasswordCheck { valid: false, needs_rehash: false })
+}
@@ -0,0 +1,133 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+
+const MAX_NAME_CHARS: usize = 40;
+
+const TONES: &[&str] = &["warm", "playful", "direct", "calm"];
+const VERBOSITIES: &[&str] = &["brief", "balanced", "detailed"];
+const PARTNER_QUESTIONS: &[&str] = &["rarely", "sometimes", "often"];
+
+/// (id, description, name, tone, verbosity, partner_questions)
+const PRESETS: &[(&str, &str, &str, &str, &str, &str)] = &[
+    ("companion", "A warm companion who gets to know you (the default)", "Jupiter", "warm", "balanced", "sometimes"),
+    ("coach", "A straight-talking matchmaking coach focused on what you're looking for", "Atlas", "direct", "brief", "often"),
+    ("friend", "A playful friend who mostly just chats", "Luna", "playful", "balanced", "rarely"),
+    ("listener", "A calm, thoughtful listener who takes its time", "Sage", "calm", "detailed", "rarely"),
+];
+
+fn preset(id: &str) -> Option<AgentPersona> {
+    PRESETS
+        .iter()
+        .find(|p| p.0 == id)
+        .map(|&(_, _, name, tone, verbosity, partner_questions)| AgentPersona {
+            name: name.to_string(),
+            tone: tone.to_string(),
+            verbosity: verbosity.to_string(),
+            partner_questions: partner_questions.to_string(),
+            updated_at: None,
+        })
+}
+
+/// The user's persona, or the default one if they haven't customized it
+pub fn get_persona_db(conn: &rusqlite::Connection, user_id: &str) -> AgentPersona {
+    conn.query_row(
+        "SELECT name, tone, verbosity, partner_questions, updated_at FROM agent_personas WHERE user_id = ?1",
+        rusqlite::params![user_id],
+        |row| {
+            Ok(AgentPersona {
+                name: row.get(0)?,
+                tone: row.get(1)?,
+                verbosity: row.get(2)?,
+                partner_questions: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        },
+    )
+    .unwrap_or_default()
+}
+
+// ── Handlers ──
+
+pub async fn get_agent_persona(
+    req: HttpRequest,
+    db: web::Data<Database>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let conn = db.conn.lock().unwrap();
+    HttpResponse::Ok().json(get_persona_db(&conn, &claims.sub))
+}
+
+pub async fn list_persona_presets() -> HttpResponse {
+    let presets: Vec<PersonaPreset> = PRESETS
+        .iter()
+        .filter_map(|&(id, description, ..)| {
+            preset(id).map(|persona| PersonaPreset {
+                id: id.to_string(),
+                description: description.to_string(),
+                persona,
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(presets)
+}
+
+pub async fn update_agent_persona(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    body: web::Json<UpdateAgentPersonaRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let body = body.into_inner();
+    let conn = db.conn.lock().unwrap();
+
+    let mut persona = match &body.preset {
+        Some(id) => match preset(id) {
+            Some(p) => p,
+            None => return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Unknown persona preset: {}", id)})),
+        },
+        None => get_persona_db(&conn, &claims.sub),
+    };
+
+    if let Some(name) = body.name {
+        let name = name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_NAME_CHARS || name.chars().any(char::is_control) {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("name must be 1 to {} characters on one line", MAX_NAME_CHARS)
+            }));
+        }
+        persona.name = name;
+    }
+    for (field, value, allowed, slot) in [
+        ("tone", body.tone, TONES, &mut persona.tone),
+        ("verbosity", body.verbosity, VERBOSITIES, &mut persona.verbosity),
+        ("partner_questions", body.partner_questions, PARTNER_QUESTIONS, &mut persona.partner_questions),
+    ] {
+        if let Some(value) = value {
+            if !allowed.contains(&value.as_str()) {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("{} must be one of: {}", field, allowed.join(", "))
+                }));
+            }
+            *slot = value;
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO agent_personas (user_id, name, tone, verbosity, partner_questions) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(user_id) DO UPDATE SET name = ?2, tone = ?3, verbosity = ?4, partner_questions = ?5, updated_at = datetime('now')",
+        rusqlite::params![&claims.sub, &persona.name, &persona.tone, &persona.verbosity, &persona.partner_questions],
+    ).unwrap();
+
+    HttpResponse::Ok().json(get_persona_db(&conn, &claims.sub))
+}
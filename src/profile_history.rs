@@ -0,0 +1,219 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+use crate::pagination::PageRequest;
+use crate::profile_locks::editable_profile;
+use crate::routes::save_agent_profile_db;
+use crate::visibility::{profile_field, PROFILE_FIELDS};
+
+const VERSION_COLUMNS: &str = "id, version, source, conversation_from_id, conversation_to_id, restored_version, prompt_version, created_at, personality_summary, interests, core_values, communication_style, looking_for, deal_breakers, raw_notes";
+
+/// Why a profile changed, stored alongside the revision
+pub enum ProfileChange {
+    /// The background updater, having read chat messages `from..=to` with the given prompt template
+    Agent { conversation_range: Option<(i64, i64)>, prompt_version: String },
+    /// A manual edit
+    User,
+    /// An accepted proposal for a locked field
+    Proposal,
+    /// Restoring an earlier version
+    Rollback { version: i64 },
+}
+
+impl ProfileChange {
+    fn source(&self) -> &'static str {
+        match self {
+            ProfileChange::Agent { .. } => "agent",
+            ProfileChange::User => "user",
+            ProfileChange::Proposal => "proposal",
+            ProfileChange::Rollback { .. } => "rollback",
+        }
+    }
+}
+
+fn insert_version(
+    conn: &rusqlite::Connection,
+    user_id: &str,
+    profile: &AgentProfile,
+    source: &str,
+    conversation_range: Option<(i64, i64)>,
+    restored_version: Option<i64>,
+    prompt_version: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO agent_profile_versions (user_id, version, personality_summary, interests, core_values, communication_style, looking_for, deal_breakers, raw_notes, source, conversation_from_id, conversation_to_id, restored_version, prompt_version)
+         VALUES (?1, (SELECT COALESCE(MAX(version), 0) + 1 FROM agent_profile_versions WHERE user_id = ?1), ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        rusqlite::params![
+            user_id,
+            &profile.personality_summary,
+            &profile.interests,
+            &profile.core_values,
+            &profile.communication_style,
+            &profile.looking_for,
+            &profile.deal_breakers,
+            &profile.raw_notes,
+            source,
+            conversation_range.map(|r| r.0),
+            conversation_range.map(|r| r.1),
+            restored_version,
+            prompt_version,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Record a new revision. Called by `save_agent_profile_db` with the profile as it was
+/// before the save, so profiles that predate versioning get a baseline to roll back to.
+pub fn record_version(
+    conn: &rusqlite::Connection,
+    user_id: &str,
+    previous: &AgentProfile,
+    profile: &AgentProfile,
+    change: &ProfileChange,
+) -> rusqlite::Result<()> {
+    let has_history: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM agent_profile_versions WHERE user_id = ?1",
+        rusqlite::params![user_id],
+        |row| row.get(0),
+    )?;
+    let previous_is_empty = PROFILE_FIELDS.iter().all(|f| profile_field(previous, f).is_empty());
+    if !has_history && !previous_is_empty {
+        insert_version(conn, user_id, previous, "baseline", None, None, None)?;
+    }
+
+    let (conversation_range, restored_version, prompt_version) = match change {
+        ProfileChange::Agent { conversation_range, prompt_version } => (*conversation_range, None, Some(prompt_version.as_str())),
+        ProfileChange::Rollback { version } => (None, Some(*version), None),
+        _ => (None, None, None),
+    };
+    insert_version(conn, user_id, profile, change.source(), conversation_range, restored_version, prompt_version)
+}
+
+fn version_row(row: &rusqlite::Row) -> rusqlite::Result<(ProfileVersion, AgentProfile)> {
+    Ok((
+        ProfileVersion {
+            id: row.get(0)?,
+            version: row.get(1)?,
+            source: row.get(2)?,
+            conversation_from_id: row.get(3)?,
+            conversation_to_id: row.get(4)?,
+            restored_version: row.get(5)?,
+            prompt_version: row.get(6)?,
+            changes: Vec::new(),
+            created_at: row.get(7)?,
+        },
+        AgentProfile {
+            personality_summary: row.get(8)?,
+            interests: row.get(9)?,
+            core_values: row.get(10)?,
+            communication_style: row.get(11)?,
+            looking_for: row.get(12)?,
+            deal_breakers: row.get(13)?,
+            raw_notes: row.get(14)?,
+            ..Default::default()
+        },
+    ))
+}
+
+fn load_version(conn: &rusqlite::Connection, user_id: &str, version: i64) -> Option<AgentProfile> {
+    conn.query_row(
+        &format!("SELECT {} FROM agent_profile_versions WHERE user_id = ?1 AND version = ?2", VERSION_COLUMNS),
+        rusqlite::params![user_id, version],
+        version_row,
+    )
+    .ok()
+    .map(|(_, profile)| profile)
+}
+
+/// Fill in each version's field-level diff against the version before it
+fn with_changes(conn: &rusqlite::Connection, user_id: &str, rows: Vec<(ProfileVersion, AgentProfile)>) -> Vec<ProfileVersion> {
+    rows.into_iter()
+        .map(|(mut version, profile)| {
+            let previous = load_version(conn, user_id, version.version - 1).unwrap_or_default();
+            version.changes = PROFILE_FIELDS
+                .iter()
+                .filter(|f| profile_field(&previous, f) != profile_field(&profile, f))
+                .map(|f| ProfileFieldChange {
+                    field: f.to_string(),
+                    before: profile_field(&previous, f).to_string(),
+                    after: profile_field(&profile, f).to_string(),
+                })
+                .collect();
+            version
+        })
+        .collect()
+}
+
+/// Every revision, oldest first (for exports)
+pub fn all_versions_db(conn: &rusqlite::Connection, user_id: &str) -> Vec<ProfileVersion> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM agent_profile_versions WHERE user_id = ?1 ORDER BY version ASC", VERSION_COLUMNS))
+        .unwrap();
+    let rows = stmt
+        .query_map(rusqlite::params![user_id], version_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    with_changes(conn, user_id, rows)
+}
+
+pub async fn get_profile_history(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    query: web::Query<PageQuery>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let page = match PageRequest::from_query(&query, 20, 100) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+
+    let conn = db.conn.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM agent_profile_versions WHERE user_id = ? AND {}", VERSION_COLUMNS, page.clause("id")))
+        .unwrap();
+    let rows = stmt
+        .query_map(rusqlite::params![&claims.sub, page.bound(), page.fetch_limit()], version_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    let versions = with_changes(&conn, &claims.sub, rows);
+
+    HttpResponse::Ok().json(page.paginate(versions, |v| v.id, false))
+}
+
+pub async fn rollback_profile(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let version = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let Some(restored) = load_version(&conn, &claims.sub, version) else {
+        return HttpResponse::NotFound().json(serde_json::json!({"error": "Version not found"}));
+    };
+
+    // A rollback is the user's own choice, so it applies to locked fields too
+    let profile = AgentProfile {
+        user_id: claims.sub.clone(),
+        updated_at: String::new(),
+        ..restored
+    };
+    if let Err(e) = save_agent_profile_db(&conn, &claims.sub, &profile, ProfileChange::Rollback { version }) {
+        log::error!("Profile rollback failed for {}: {}", claims.sub, e);
+        return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to roll back profile"}));
+    }
+
+    HttpResponse::Ok().json(editable_profile(&conn, &claims.sub))
+}
@@ -0,0 +1,205 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+use crate::profile_history::ProfileChange;
+use crate::routes::{get_agent_profile_db, save_agent_profile_db};
+use crate::visibility::{profile_field, profile_field_mut, PROFILE_FIELDS};
+
+const MAX_FIELD_CHARS: usize = 4000;
+
+const PROPOSAL_COLUMNS: &str = "id, field, current_value, proposed_value, status, created_at, resolved_at";
+
+fn proposal_from_row(row: &rusqlite::Row) -> rusqlite::Result<ProfileProposal> {
+    Ok(ProfileProposal {
+        id: row.get(0)?,
+        field: row.get(1)?,
+        current_value: row.get(2)?,
+        proposed_value: row.get(3)?,
+        status: row.get(4)?,
+        created_at: row.get(5)?,
+        resolved_at: row.get(6)?,
+    })
+}
+
+pub fn locked_fields_db(conn: &rusqlite::Connection, user_id: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare("SELECT field FROM agent_profile_locks WHERE user_id = ?1 ORDER BY field")
+        .unwrap();
+    stmt.query_map(rusqlite::params![user_id], |row| row.get(0))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+pub fn list_proposals_db(conn: &rusqlite::Connection, user_id: &str, pending_only: bool) -> Vec<ProfileProposal> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_profile_proposals WHERE user_id = ?1 AND (?2 = 0 OR status = 'pending') ORDER BY id ASC",
+            PROPOSAL_COLUMNS
+        ))
+        .unwrap();
+    stmt.query_map(rusqlite::params![user_id, pending_only], proposal_from_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+pub fn editable_profile(conn: &rusqlite::Connection, user_id: &str) -> EditableAgentProfile {
+    EditableAgentProfile {
+        profile: get_agent_profile_db(conn, user_id),
+        locked_fields: locked_fields_db(conn, user_id),
+        pending_proposals: list_proposals_db(conn, user_id, true),
+    }
+}
+
+/// Take an LLM-updated profile and put the stored value back into every locked field.
+/// Where the agent wanted something different, a pending proposal replaces any earlier one.
+pub fn apply_locks(conn: &rusqlite::Connection, user_id: &str, mut updated: AgentProfile) -> AgentProfile {
+    let locked = locked_fields_db(conn, user_id);
+    if locked.is_empty() {
+        return updated;
+    }
+
+    // Read the stored profile now rather than trusting the one the LLM started from,
+    // in case the user edited it while the update was running
+    let stored = get_agent_profile_db(conn, user_id);
+    for field in &locked {
+        let current = profile_field(&stored, field).to_string();
+        let Some(slot) = profile_field_mut(&mut updated, field) else {
+            continue;
+        };
+        let proposed = std::mem::replace(slot, current.clone());
+        if proposed.trim().is_empty() || proposed.trim() == current.trim() {
+            continue;
+        }
+
+        let _ = conn.execute(
+            "UPDATE agent_profile_proposals SET status = 'superseded', resolved_at = datetime('now') WHERE user_id = ?1 AND field = ?2 AND status = 'pending'",
+            rusqlite::params![user_id, field],
+        );
+        if let Err(e) = conn.execute(
+            "INSERT INTO agent_profile_proposals (user_id, field, current_value, proposed_value) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![user_id, field, &current, &proposed],
+        ) {
+            log::error!("Failed to record profile proposal for {}: {}", user_id, e);
+        }
+    }
+    updated
+}
+
+// ── Handlers ──
+
+pub async fn update_agent_profile(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    body: web::Json<UpdateAgentProfileRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let body = body.into_inner();
+    let lock_changes = body.locked.unwrap_or_default();
+    if let Some(field) = body.fields.keys().chain(lock_changes.keys()).find(|f| !PROFILE_FIELDS.contains(&f.as_str())) {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Unknown profile field: {}", field)}));
+    }
+
+    let edits: Vec<(&str, String)> = PROFILE_FIELDS
+        .iter()
+        .filter_map(|&field| body.fields.get(field).and_then(|v| v.as_deref()).map(|v| (field, v.trim().to_string())))
+        .collect();
+
+    if let Some((field, _)) = edits.iter().find(|(_, v)| v.chars().count() > MAX_FIELD_CHARS) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("{} must be at most {} characters", field, MAX_FIELD_CHARS)
+        }));
+    }
+
+    let conn = db.conn.lock().unwrap();
+
+    if !edits.is_empty() {
+        let mut profile = get_agent_profile_db(&conn, &claims.sub);
+        for (field, value) in &edits {
+            if let Some(slot) = profile_field_mut(&mut profile, field) {
+                *slot = value.clone();
+            }
+            // The user's own edit answers whatever the agent had proposed for this field
+            conn.execute(
+                "UPDATE agent_profile_proposals SET status = 'superseded', resolved_at = datetime('now') WHERE user_id = ?1 AND field = ?2 AND status = 'pending'",
+                rusqlite::params![&claims.sub, field],
+            ).unwrap();
+        }
+        save_agent_profile_db(&conn, &claims.sub, &profile, ProfileChange::User).unwrap();
+    }
+
+    // Edited fields are locked so the next background update doesn't undo the correction
+    let mut locks: Vec<(String, bool)> = edits
+        .iter()
+        .filter(|(field, _)| !lock_changes.contains_key(*field))
+        .map(|(field, _)| (field.to_string(), true))
+        .collect();
+    locks.extend(lock_changes);
+    for (field, lock) in &locks {
+        if *lock {
+            conn.execute(
+                "INSERT OR IGNORE INTO agent_profile_locks (user_id, field) VALUES (?1, ?2)",
+                rusqlite::params![&claims.sub, field],
+            ).unwrap();
+        } else {
+            conn.execute(
+                "DELETE FROM agent_profile_locks WHERE user_id = ?1 AND field = ?2",
+                rusqlite::params![&claims.sub, field],
+            ).unwrap();
+        }
+    }
+
+    HttpResponse::Ok().json(editable_profile(&conn, &claims.sub))
+}
+
+pub async fn resolve_profile_proposal(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(i64, String)>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (proposal_id, decision) = path.into_inner();
+    let status = match decision.as_str() {
+        "accept" => "accepted",
+        "reject" => "rejected",
+        _ => return HttpResponse::NotFound().json(serde_json::json!({"error": "Not found"})),
+    };
+
+    let conn = db.conn.lock().unwrap();
+    let proposal = match conn.query_row(
+        &format!("SELECT {} FROM agent_profile_proposals WHERE id = ?1 AND user_id = ?2", PROPOSAL_COLUMNS),
+        rusqlite::params![proposal_id, &claims.sub],
+        proposal_from_row,
+    ) {
+        Ok(p) => p,
+        Err(_) => return HttpResponse::NotFound().json(serde_json::json!({"error": "Proposal not found"})),
+    };
+    if proposal.status != "pending" {
+        return HttpResponse::Conflict().json(serde_json::json!({"error": format!("Proposal is already {}", proposal.status)}));
+    }
+
+    if status == "accepted" {
+        let mut profile = get_agent_profile_db(&conn, &claims.sub);
+        if let Some(slot) = profile_field_mut(&mut profile, &proposal.field) {
+            *slot = proposal.proposed_value.clone();
+        }
+        save_agent_profile_db(&conn, &claims.sub, &profile, ProfileChange::Proposal).unwrap();
+    }
+    conn.execute(
+        "UPDATE agent_profile_proposals SET status = ?1, resolved_at = datetime('now') WHERE id = ?2",
+        rusqlite::params![status, proposal_id],
+    ).unwrap();
+
+    HttpResponse::Ok().json(editable_profile(&conn, &claims.sub))
+}
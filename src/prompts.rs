@@ -0,0 +1,131 @@
+use minijinja::{Environment, ErrorKind};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
+
+/// Every template the agent renders, with the copy built into the binary. The built-in copy
+/// is used when `PROMPTS_DIR` has no such file or the file doesn't compile.
+const TEMPLATES: &[(&str, &str)] = &[
+    ("chat", include_str!("../prompts/chat.j2")),
+    ("profile_update", include_str!("../prompts/profile_update.j2")),
+    ("compatibility", include_str!("../prompts/compatibility.j2")),
+    ("summary", include_str!("../prompts/summary.j2")),
+    ("icebreakers", include_str!("../prompts/icebreakers.j2")),
+    ("reply_suggestions", include_str!("../prompts/reply_suggestions.j2")),
+    ("moderation", include_str!("../prompts/moderation.j2")),
+];
+
+/// A rendered prompt and the version of the template it came from
+pub struct RenderedPrompt {
+    pub system: String,
+    pub prompt: String,
+    pub version: String,
+}
+
+/// Prompt templates loaded from disk. A template is re-read the next time it is rendered
+/// after its file changes, so prompts can be edited without a restart.
+pub struct PromptTemplates {
+    dir: PathBuf,
+    env: RwLock<Environment<'static>>,
+    versions: RwLock<HashMap<String, String>>,
+    modified: Mutex<HashMap<String, Option<SystemTime>>>,
+}
+
+/// `name@N+<hash>` from a leading `{# version: N #}` comment and a hash of the source, or
+/// `name@<hash>` when there's no comment. The hash ties stored outputs to the exact template
+/// text, even when an edit forgets to bump the declared version.
+fn template_version(name: &str, source: &str) -> String {
+    // FNV-1a; stable across builds, unlike the std hasher
+    let hash = source.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3)) as u32;
+    let declared = regex::Regex::new(r"^\s*\{#\s*version:\s*([\w.\-]+)\s*#\}")
+        .unwrap()
+        .captures(source)
+        .map(|c| c[1].to_string());
+    match declared {
+        Some(v) => format!("{}@{}+{:08x}", name, v, hash),
+        None => format!("{}@{:08x}", name, hash),
+    }
+}
+
+impl PromptTemplates {
+    pub fn from_env() -> Self {
+        let templates = PromptTemplates {
+            dir: PathBuf::from(std::env::var("PROMPTS_DIR").unwrap_or_else(|_| "prompts".to_string())),
+            env: RwLock::new(Environment::new()),
+            versions: RwLock::new(HashMap::new()),
+            modified: Mutex::new(HashMap::new()),
+        };
+        for (name, _) in TEMPLATES {
+            templates.load(name);
+        }
+        templates
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.j2", name))
+    }
+
+    fn modified_at(&self, name: &str) -> Option<SystemTime> {
+        std::fs::metadata(self.path(name)).and_then(|m| m.modified()).ok()
+    }
+
+    /// (Re)load one template: the file if it compiles, otherwise whatever was loaded before,
+    /// otherwise the built-in copy
+    fn load(&self, name: &str) {
+        let builtin = TEMPLATES.iter().find(|(n, _)| *n == name).map(|(_, s)| *s).unwrap_or_default();
+        self.modified.lock().unwrap().insert(name.to_string(), self.modified_at(name));
+
+        let path = self.path(name);
+        let (source, origin) = match std::fs::read_to_string(&path) {
+            Ok(s) => (s, path.display().to_string()),
+            Err(_) => (builtin.to_string(), "built-in".to_string()),
+        };
+
+        let mut env = self.env.write().unwrap();
+        if let Err(e) = env.add_template_owned(name.to_string(), source.clone()) {
+            let loaded = self.versions.read().unwrap().contains_key(name);
+            log::error!("Prompt template {} failed to compile, keeping the {} one: {}", origin, if loaded { "previous" } else { "built-in" }, e);
+            if !loaded {
+                env.add_template_owned(name.to_string(), builtin.to_string()).expect("built-in prompt template");
+                self.versions.write().unwrap().insert(name.to_string(), template_version(name, builtin));
+            }
+            return;
+        }
+
+        let version = template_version(name, &source);
+        log::info!("Loaded prompt template {} from {}", version, origin);
+        self.versions.write().unwrap().insert(name.to_string(), version);
+    }
+
+    /// Render the `system` block and, when the template has one, the `prompt` block
+    pub fn render<S: Serialize>(&self, name: &str, ctx: S) -> Result<RenderedPrompt, String> {
+        let changed = self.modified.lock().unwrap().get(name) != Some(&self.modified_at(name));
+        if changed {
+            self.load(name);
+        }
+
+        let env = self.env.read().unwrap();
+        let template = env.get_template(name).map_err(|e| format!("Prompt template {}: {}", name, e))?;
+        let mut captured = template
+            .render_captured(ctx)
+            .map_err(|e| format!("Prompt template {}: {}", name, e))?;
+        let (system, prompt) = captured.with_state_mut(|state| {
+            let system = state.render_block("system")?;
+            let prompt = match state.render_block("prompt") {
+                Ok(p) => p,
+                Err(e) if e.kind() == ErrorKind::UnknownBlock => String::new(),
+                Err(e) => return Err(e),
+            };
+            Ok((system, prompt))
+        })
+        .map_err(|e: minijinja::Error| format!("Prompt template {}: {}", name, e))?;
+
+        Ok(RenderedPrompt {
+            system: system.trim().to_string(),
+            prompt: prompt.trim().to_string(),
+            version: self.versions.read().unwrap().get(name).cloned().unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,344 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::Message;
+use futures_util::stream;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::auth::extract_user_id_or_query;
+use crate::broker::Broker;
+use crate::db::Database;
+use crate::models::*;
+
+/// Users this user shares a confirmed match with
+pub fn match_partners(conn: &rusqlite::Connection, user_id: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT CASE WHEN user_a_id = ?1 THEN user_b_id ELSE user_a_id END
+             FROM matches WHERE is_matched = 1 AND (user_a_id = ?1 OR user_b_id = ?1)",
+        )
+        .unwrap();
+    stmt.query_map(rusqlite::params![user_id], |row| row.get(0))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+/// Both participants of a confirmed match, if `user_id` is one of them
+pub fn match_participants(conn: &rusqlite::Connection, match_id: i64, user_id: &str) -> Option<(String, String)> {
+    conn.query_row(
+        "SELECT user_a_id, user_b_id FROM matches WHERE id = ?1 AND is_matched = 1 AND (user_a_id = ?2 OR user_b_id = ?2)",
+        rusqlite::params![match_id, user_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .ok()
+}
+
+/// Send an event to both participants of a match (the sender too, for their other devices)
+pub fn publish_to_match(broker: &dyn Broker, participants: &(String, String), event: RealtimeEvent) {
+    broker.publish(&participants.0, event.clone());
+    broker.publish(&participants.1, event);
+}
+
+/// A live subscription to a user's events. Any open subscription (WebSocket or SSE) counts as
+/// the user being online; partners are told when the first one opens and the last one closes.
+pub struct Subscription {
+    events: Option<broadcast::Receiver<RealtimeEvent>>,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    user_id: String,
+}
+
+impl Subscription {
+    pub fn open(db: web::Data<Database>, broker: web::Data<dyn Broker>, user_id: &str) -> Self {
+        let was_online = broker.is_online(user_id);
+        let events = broker.subscribe(user_id);
+        if !was_online {
+            announce_presence(&db, broker.get_ref(), user_id, true);
+        }
+
+        Subscription {
+            events: Some(events),
+            db,
+            broker,
+            user_id: user_id.to_string(),
+        }
+    }
+
+    pub async fn recv(&mut self) -> Result<RealtimeEvent, RecvError> {
+        match self.events.as_mut() {
+            Some(events) => events.recv().await,
+            None => Err(RecvError::Closed),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.events = None;
+        if !self.broker.is_online(&self.user_id) {
+            announce_presence(&self.db, self.broker.get_ref(), &self.user_id, false);
+        }
+    }
+}
+
+fn announce_presence(db: &Database, broker: &dyn Broker, user_id: &str, online: bool) {
+    let partners = {
+        let conn = db.conn.lock().unwrap();
+        match_partners(&conn, user_id)
+    };
+    for partner in partners {
+        broker.publish(
+            &partner,
+            RealtimeEvent::Presence {
+                user_id: user_id.to_string(),
+                online,
+            },
+        );
+    }
+}
+
+pub async fn ws_connect(
+    req: HttpRequest,
+    body: web::Payload,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+) -> HttpResponse {
+    let claims = match extract_user_id_or_query(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (response, mut session, mut stream) = match actix_ws::handle(&req, body) {
+        Ok(parts) => parts,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+
+    let user_id = claims.sub;
+    let mut subscription = Subscription::open(db.clone(), broker.clone(), &user_id);
+
+    // Tell the new connection which partners are already online
+    let partners = {
+        let conn = db.conn.lock().unwrap();
+        match_partners(&conn, &user_id)
+    };
+    for partner in partners.into_iter().filter(|p| broker.is_online(p)) {
+        let snapshot = RealtimeEvent::Presence { user_id: partner, online: true };
+        if let Ok(json) = serde_json::to_string(&snapshot) {
+            let _ = session.text(json).await;
+        }
+    }
+
+    actix_rt::spawn(async move {
+        let mut heartbeat = tokio::time::interval(Duration::from_secs(30));
+
+        loop {
+            tokio::select! {
+                event = subscription.recv() => match event {
+                    Ok(event) => {
+                        let Ok(json) = serde_json::to_string(&event) else { continue };
+                        if session.text(json).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("WebSocket for {} lagged, skipped {} events", user_id, skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                msg = stream.recv() => match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        handle_client_event(&db, broker.get_ref(), &user_id, &text);
+                    }
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+                _ = heartbeat.tick() => {
+                    if session.ping(b"").await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        drop(subscription);
+        let _ = session.close(None).await;
+    });
+
+    response.map_into_boxed_body()
+}
+
+fn handle_client_event(db: &Database, broker: &dyn Broker, user_id: &str, text: &str) {
+    let event: ClientEvent = match serde_json::from_str(text) {
+        Ok(e) => e,
+        Err(e) => {
+            log::debug!("Ignoring malformed WebSocket message from {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    match event {
+        ClientEvent::Typing { match_id, is_typing } => {
+            let participants = {
+                let conn = db.conn.lock().unwrap();
+                match_participants(&conn, match_id, user_id)
+            };
+            if let Some((a, b)) = participants {
+                let other = if a == user_id { b } else { a };
+                broker.publish(
+                    &other,
+                    RealtimeEvent::Typing {
+                        match_id,
+                        user_id: user_id.to_string(),
+                        is_typing,
+                    },
+                );
+            }
+        }
+    }
+}
+
+// ── Notification stream (SSE) ──
+
+/// Missed notifications are replayed from the database this many at a time
+const REPLAY_BATCH: i64 = 500;
+
+struct NotificationStream {
+    subscription: Subscription,
+    db: web::Data<Database>,
+    user_id: String,
+    backlog: VecDeque<Notification>,
+    last_id: i64,
+    /// Whether everything up to now has been read from the database; until then live
+    /// events are ignored, since the replay will deliver them in order
+    caught_up: bool,
+    keepalive: tokio::time::Interval,
+}
+
+/// The next batch of the user's notifications after `after_id`, oldest first
+fn notifications_after(conn: &rusqlite::Connection, user_id: &str, after_id: i64) -> VecDeque<Notification> {
+    let mut stmt = conn
+        .prepare("SELECT id, user_id, notification_type, title, message, related_user_id, is_read, created_at FROM notifications WHERE user_id = ?1 AND id > ?2 ORDER BY id ASC LIMIT ?3")
+        .unwrap();
+    stmt.query_map(rusqlite::params![user_id, after_id, REPLAY_BATCH], |row| {
+        Ok(Notification {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            notification_type: row.get(2)?,
+            title: row.get(3)?,
+            message: row.get(4)?,
+            related_user_id: row.get(5)?,
+            is_read: row.get::<_, i32>(6)? != 0,
+            created_at: row.get(7)?,
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
+fn sse_frame(notification: &Notification) -> web::Bytes {
+    let data = serde_json::to_string(notification).unwrap_or_default();
+    web::Bytes::from(format!("id: {}\nevent: notification\ndata: {}\n\n", notification.id, data))
+}
+
+pub async fn notification_stream(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    query: web::Query<NotificationStreamQuery>,
+) -> HttpResponse {
+    let claims = match extract_user_id_or_query(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    // EventSource sends Last-Event-ID on reconnect; the query param covers the first connection
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .or(query.last_event_id);
+
+    // A fresh connection only wants what arrives from now on; read the high-water mark first
+    let since = match last_event_id {
+        Some(id) => id,
+        None => {
+            let conn = db.conn.lock().unwrap();
+            conn.query_row(
+                "SELECT COALESCE(MAX(id), 0) FROM notifications WHERE user_id = ?1",
+                rusqlite::params![&claims.sub],
+                |row| row.get(0),
+            )
+            .unwrap_or(0)
+        }
+    };
+
+    // Subscribe before reading the backlog so nothing inserted in between is lost
+    let subscription = Subscription::open(db.clone(), broker.clone(), &claims.sub);
+
+    let state = NotificationStream {
+        subscription,
+        db: db.clone(),
+        user_id: claims.sub.clone(),
+        backlog: VecDeque::new(),
+        last_id: since,
+        caught_up: false,
+        keepalive: tokio::time::interval(Duration::from_secs(15)),
+    };
+
+    let body = stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(notification) = state.backlog.pop_front() {
+                state.last_id = state.last_id.max(notification.id);
+                let frame = sse_frame(&notification);
+                return Some((Ok::<_, actix_web::Error>(frame), state));
+            }
+
+            // Replay from the database in batches until a short batch shows we've caught up
+            if !state.caught_up {
+                state.backlog = {
+                    let conn = state.db.conn.lock().unwrap();
+                    notifications_after(&conn, &state.user_id, state.last_id)
+                };
+                state.caught_up = (state.backlog.len() as i64) < REPLAY_BATCH;
+                continue;
+            }
+
+            tokio::select! {
+                event = state.subscription.recv() => match event {
+                    // Skip anything the replay already delivered
+                    Ok(RealtimeEvent::Notification { notification }) if notification.id > state.last_id => {
+                        state.last_id = notification.id;
+                        let frame = sse_frame(&notification);
+                        return Some((Ok(frame), state));
+                    }
+                    Ok(_) => continue,
+                    // Dropped events are still in the database; go back and replay them
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Notification stream lagged by {} events, replaying from the database", skipped);
+                        state.caught_up = false;
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = state.keepalive.tick() => {
+                    return Some((Ok(web::Bytes::from_static(b": keep-alive\n\n")), state));
+                }
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(body)
+}
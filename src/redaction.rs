@@ -0,0 +1,285 @@
+use regex::Regex;
+
+use crate::models::LlmMessage;
+
+/// Kinds of personal data the redactor knows how to find
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiKind {
+    Email,
+    Url,
+    Card,
+    Phone,
+    Address,
+    Name,
+}
+
+impl PiiKind {
+    const ALL: [PiiKind; 6] = [PiiKind::Email, PiiKind::Url, PiiKind::Card, PiiKind::Phone, PiiKind::Address, PiiKind::Name];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "email",
+            PiiKind::Url => "url",
+            PiiKind::Card => "card",
+            PiiKind::Phone => "phone",
+            PiiKind::Address => "address",
+            PiiKind::Name => "name",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "EMAIL",
+            PiiKind::Url => "URL",
+            PiiKind::Card => "CARD",
+            PiiKind::Phone => "PHONE",
+            PiiKind::Address => "ADDRESS",
+            PiiKind::Name => "NAME",
+        }
+    }
+
+    fn pattern(&self) -> &'static str {
+        match self {
+            PiiKind::Email => r"(?i)\b[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}\b",
+            PiiKind::Url => r"(?i)\b(?:https?://|www\.)[^\s<>()\[\]]+",
+            PiiKind::Card => r"\b(?:\d[ -]?){12,18}\d\b",
+            PiiKind::Phone => r"(?:\+\d{1,3}[\s.-]?)?\(?\d{2,4}\)?[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b",
+            PiiKind::Address => r"(?i)\b\d{1,5}\s+(?:[a-z][a-z'-]*\s+){1,3}(?:street|st|avenue|ave|road|rd|boulevard|blvd|lane|ln|drive|dr|court|ct|way|place|pl|terrace|square|sq)\b\.?(?:,?\s*(?:apt|apartment|unit|flat)\.?\s*#?\w+)?",
+            // Full names are recognised when someone introduces themselves; every later
+            // mention of the same name is then redacted too, as are names passed in as known
+            PiiKind::Name => r"(?:\b[Mm]y name is|\b[Ii]'m|\b[Ii] am|\b[Cc]all me|\b[Nn]ame's)\s+([A-Z][a-z'-]+(?:\s+[A-Z][a-z'-]+)+)",
+        }
+    }
+}
+
+/// Placeholder ↔ original value pairs for one LLM call. The same value always gets the
+/// same placeholder, so the model can still tell two different emails apart.
+#[derive(Debug, Default)]
+pub struct PiiVault {
+    entries: Vec<(String, String)>,
+}
+
+impl PiiVault {
+    fn placeholder_for(&mut self, kind: PiiKind, value: &str) -> String {
+        if let Some((placeholder, _)) = self.entries.iter().find(|(_, v)| v == value) {
+            return placeholder.clone();
+        }
+        let n = self.entries.iter().filter(|(p, _)| p.starts_with(&format!("[{}_", kind.label()))).count() + 1;
+        let placeholder = format!("[{}_{}]", kind.label(), n);
+        self.entries.push((placeholder.clone(), value.to_string()));
+        placeholder
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Put the original values back into text the model wrote
+    pub fn restore(&self, text: &str) -> String {
+        self.entries
+            .iter()
+            .fold(text.to_string(), |acc, (placeholder, value)| acc.replace(placeholder, value))
+    }
+}
+
+/// Replaces personal data in outgoing prompts with placeholders
+pub struct Redactor {
+    rules: Vec<(PiiKind, Regex)>,
+}
+
+impl Redactor {
+    /// `PII_REDACT` is a comma-separated list of kinds (email, url, card, phone, address, name).
+    /// Unset means all of them; empty or `none` turns redaction off.
+    pub fn from_env() -> Self {
+        let kinds: Vec<PiiKind> = match std::env::var("PII_REDACT") {
+            Err(_) => PiiKind::ALL.to_vec(),
+            Ok(list) => {
+                let wanted: Vec<String> = list.split(',').map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty()).collect();
+                for unknown in wanted.iter().filter(|k| *k != "none" && !PiiKind::ALL.iter().any(|p| p.as_str() == k.as_str())) {
+                    log::warn!("Ignoring unknown PII_REDACT entity type: {}", unknown);
+                }
+                PiiKind::ALL.into_iter().filter(|k| wanted.iter().any(|w| w == k.as_str())).collect()
+            }
+        };
+
+        // ALL is already in a safe order: emails before URLs, and cards before phone numbers,
+        // so the broader patterns don't claim pieces of the narrower ones
+        Redactor {
+            rules: kinds
+                .into_iter()
+                .map(|k| (k, Regex::new(k.pattern()).expect("invalid PII pattern")))
+                .collect(),
+        }
+    }
+
+    /// Full names people introduced themselves with in `texts` ("my name is …", "I'm …"),
+    /// so they can be hidden later where no introduction appears. Empty when names aren't redacted.
+    pub fn introduced_names<'a>(&self, texts: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let Some((_, re)) = self.rules.iter().find(|(kind, _)| *kind == PiiKind::Name) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = Vec::new();
+        for text in texts {
+            for name in re.captures_iter(text).filter_map(|c| c.get(1)) {
+                if !names.iter().any(|n| n == name.as_str()) {
+                    names.push(name.as_str().to_string());
+                }
+            }
+        }
+        names
+    }
+
+    /// Redact every message in place, sharing one vault across the whole conversation.
+    /// `known_names` are names already on record for the people in the prompt (display
+    /// names, earlier introductions); they're redacted even when nobody introduces
+    /// themselves in these messages.
+    pub fn redact_messages(&self, messages: &mut [LlmMessage], known_names: &[String]) -> PiiVault {
+        let mut vault = PiiVault::default();
+        if self.rules.is_empty() {
+            return vault;
+        }
+
+        // Names are found first, across all messages, so a name introduced early in the
+        // history is also caught wherever it's mentioned on its own later
+        let mut names = self.introduced_names(messages.iter().map(|m| m.content.as_str()));
+        if self.rules.iter().any(|(kind, _)| *kind == PiiKind::Name) {
+            names.extend(known_names.iter().cloned());
+        }
+        let name_re = name_pattern(&names);
+
+        for message in messages.iter_mut() {
+            let mut text = std::mem::take(&mut message.content);
+            for (kind, re) in self.rules.iter().filter(|(kind, _)| *kind != PiiKind::Name) {
+                text = re
+                    .replace_all(&text, |caps: &regex::Captures| vault.placeholder_for(*kind, &caps[0]))
+                    .into_owned();
+            }
+            if let Some(re) = &name_re {
+                text = re
+                    .replace_all(&text, |caps: &regex::Captures| vault.placeholder_for(PiiKind::Name, &caps[0]))
+                    .into_owned();
+            }
+            message.content = text;
+        }
+
+        vault
+    }
+}
+
+/// Matches each of `names`, and each part of it on its own ("Anna" of "Anna Kowalski"),
+/// as whole words. `None` when there's nothing to match.
+fn name_pattern(names: &[String]) -> Option<Regex> {
+    let mut parts: Vec<&str> = names
+        .iter()
+        .flat_map(|n| std::iter::once(n.trim()).chain(n.split_whitespace()))
+        .filter(|p| p.chars().count() >= 2)
+        .collect();
+    if parts.is_empty() {
+        return None;
+    }
+    // Longest first, so a full name is replaced once rather than part by part
+    parts.sort_by_key(|p| std::cmp::Reverse(p.len()));
+    parts.dedup();
+    Regex::new(&format!(r"\b(?:{})\b", parts.iter().map(|p| regex::escape(p)).collect::<Vec<_>>().join("|"))).ok()
+}
+
+/// Replace each known name, and each part of it on its own, with `[NAME]`. For stored
+/// text about a user that is about to be shown to someone else.
+pub fn redact_known_names(text: &str, names: &[String]) -> String {
+    match name_pattern(names) {
+        Some(re) => re.replace_all(text, "[NAME]").into_owned(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor() -> Redactor {
+        Redactor {
+            rules: PiiKind::ALL
+                .into_iter()
+                .map(|k| (k, Regex::new(k.pattern()).unwrap()))
+                .collect(),
+        }
+    }
+
+    fn message(content: &str) -> LlmMessage {
+        LlmMessage { role: "user".to_string(), content: content.to_string() }
+    }
+
+    fn redact(text: &str) -> String {
+        let mut messages = vec![message(text)];
+        redactor().redact_messages(&mut messages, &[]);
+        messages.remove(0).content
+    }
+
+    #[test]
+    fn redacts_each_kind() {
+        assert_eq!(redact("mail me at anna.k+x@example.co.uk please"), "mail me at [EMAIL_1] please");
+        assert_eq!(redact("see https://example.com/me?x=1 or www.example.org"), "see [URL_1] or [URL_2]");
+        assert_eq!(redact("card 4111 1111 1111 1111 ok"), "card [CARD_1] ok");
+        assert_eq!(redact("call +48 601-234-567 tonight"), "call [PHONE_1] tonight");
+        assert_eq!(redact("I live at 221 Baker Street, apt 4"), "I live at [ADDRESS_1]");
+        assert_eq!(redact("Hi, my name is Anna Kowalski"), "Hi, my name is [NAME_1]");
+    }
+
+    #[test]
+    fn leaves_ordinary_text_alone() {
+        let text = "I'm 29, love hiking and have 2 cats. Anna is my favourite name.";
+        assert_eq!(redact(text), text);
+    }
+
+    #[test]
+    fn email_is_not_mistaken_for_a_url_and_card_not_for_a_phone() {
+        assert_eq!(redact("bob@www.example.com"), "[EMAIL_1]");
+        assert_eq!(redact("5500 0000 0000 0004"), "[CARD_1]");
+    }
+
+    #[test]
+    fn introduced_names_are_redacted_everywhere() {
+        let mut messages = vec![message("I'm Anna Kowalski"), message("Anna Kowalski again, write to a@b.com")];
+        let vault = redactor().redact_messages(&mut messages, &[]);
+        assert_eq!(messages[0].content, "I'm [NAME_1]");
+        assert_eq!(messages[1].content, "[NAME_1] again, write to [EMAIL_1]");
+        assert_eq!(vault.restore("Hello [NAME_1] ([EMAIL_1])"), "Hello Anna Kowalski (a@b.com)");
+    }
+
+    #[test]
+    fn known_names_are_redacted_without_an_introduction() {
+        let known = vec!["Anna Kowalski".to_string(), "annak".to_string()];
+        let mut messages = vec![message("Anna went hiking"), message("Kowalski, aka annak, says hi to Annabel")];
+        let vault = redactor().redact_messages(&mut messages, &known);
+        assert_eq!(messages[0].content, "[NAME_1] went hiking");
+        assert_eq!(messages[1].content, "[NAME_2], aka [NAME_3], says hi to Annabel");
+        assert_eq!(vault.restore("[NAME_1] [NAME_2]"), "Anna Kowalski");
+    }
+
+    #[test]
+    fn same_value_gets_same_placeholder() {
+        assert_eq!(redact("a@b.com, c@d.com, a@b.com"), "[EMAIL_1], [EMAIL_2], [EMAIL_1]");
+    }
+
+    #[test]
+    fn no_rules_means_no_redaction() {
+        let mut messages = vec![message("a@b.com")];
+        let vault = Redactor { rules: Vec::new() }.redact_messages(&mut messages, &["Anna".to_string()]);
+        assert!(vault.is_empty());
+        assert_eq!(messages[0].content, "a@b.com");
+    }
+
+    #[test]
+    fn finds_introduced_names() {
+        let names = redactor().introduced_names(["My name is Anna Kowalski", "call me Jan Nowak", "I'm tired", "I'm Anna Kowalski"]);
+        assert_eq!(names, vec!["Anna Kowalski".to_string(), "Jan Nowak".to_string()]);
+    }
+
+    #[test]
+    fn redacts_known_names_and_their_parts() {
+        let names = vec!["Anna Kowalski".to_string()];
+        assert_eq!(redact_known_names("Anna Kowalski likes tea; Anna hikes", &names), "[NAME] likes tea; [NAME] hikes");
+        assert_eq!(redact_known_names("Annabel is someone else", &names), "Annabel is someone else");
+        assert_eq!(redact_known_names("unchanged", &[]), "unchanged");
+    }
+}
@@ -0,0 +1,266 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+use crate::realtime::match_participants;
+use crate::routes::{direct_message_from_row, load_message_details, DM_COLUMNS};
+
+const REPORT_REASONS: &[&str] = &["harassment", "threat", "scam", "spam", "inappropriate", "fake_profile", "underage", "other"];
+
+pub const CASE_COLUMNS: &str = "id, reporter_id, reported_user_id, target_type, message_id, reason, details, snapshot, status, priority, assigned_to, resolution, created_at, updated_at, closed_at";
+
+pub fn case_from_row(row: &rusqlite::Row) -> rusqlite::Result<ModerationCase> {
+    let snapshot: String = row.get(7)?;
+    Ok(ModerationCase {
+        id: row.get(0)?,
+        reporter_id: row.get(1)?,
+        reported_user_id: row.get(2)?,
+        target_type: row.get(3)?,
+        message_id: row.get(4)?,
+        reason: row.get(5)?,
+        details: row.get(6)?,
+        snapshot: serde_json::from_str(&snapshot).unwrap_or(serde_json::Value::Null),
+        status: row.get(8)?,
+        priority: row.get(9)?,
+        assigned_to: row.get(10)?,
+        resolution: row.get(11)?,
+        created_at: row.get(12)?,
+        updated_at: row.get(13)?,
+        closed_at: row.get(14)?,
+    })
+}
+
+/// Append a step to a case's audit trail. `actor_id` is None for system events.
+pub fn record_case_event(
+    conn: &rusqlite::Connection,
+    case_id: i64,
+    actor_id: Option<&str>,
+    event_type: &str,
+    details: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO moderation_case_events (case_id, actor_id, event_type, details) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![case_id, actor_id, event_type, details],
+    )?;
+    Ok(())
+}
+
+fn shares_match(conn: &rusqlite::Connection, a: &str, b: &str) -> bool {
+    conn.query_row(
+        "SELECT COUNT(*) FROM matches WHERE (user_a_id = ?1 AND user_b_id = ?2) OR (user_a_id = ?2 AND user_b_id = ?1)",
+        rusqlite::params![a, b],
+        |row| row.get::<_, i64>(0),
+    )
+    .unwrap_or(0)
+        > 0
+}
+
+fn public_profile_snapshot(conn: &rusqlite::Connection, user_id: &str) -> Option<serde_json::Value> {
+    conn.query_row(
+        "SELECT id, username, display_name, bio FROM users WHERE id = ?1",
+        rusqlite::params![user_id],
+        |row| {
+            Ok(serde_json::json!({
+                "id": row.get::<_, String>(0)?,
+                "username": row.get::<_, String>(1)?,
+                "display_name": row.get::<_, String>(2)?,
+                "bio": row.get::<_, String>(3)?,
+            }))
+        },
+    )
+    .ok()
+}
+
+/// The reported user's recent messages to the reporter, across every match they share
+fn recent_messages_snapshot(conn: &rusqlite::Connection, reporter_id: &str, reported_id: &str) -> Vec<DirectMessage> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM direct_messages
+             WHERE sender_id = ?2 AND match_id IN (SELECT id FROM matches WHERE (user_a_id = ?1 AND user_b_id = ?2) OR (user_a_id = ?2 AND user_b_id = ?1))
+             ORDER BY id DESC LIMIT 50",
+            DM_COLUMNS
+        ))
+        .unwrap();
+    let mut messages: Vec<DirectMessage> = stmt
+        .query_map(rusqlite::params![reporter_id, reported_id], direct_message_from_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    messages.reverse();
+    load_message_details(conn, &mut messages);
+    messages
+}
+
+pub async fn create_report(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    body: web::Json<CreateReportRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    if !REPORT_REASONS.contains(&body.reason.as_str()) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("reason must be one of: {}", REPORT_REASONS.join(", "))
+        }));
+    }
+    let details = body.details.clone().unwrap_or_default().trim().to_string();
+    if details.chars().count() > 4000 {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "details must be at most 4000 characters"}));
+    }
+
+    let conn = db.conn.lock().unwrap();
+
+    // Work out who is being reported and freeze the evidence as it is right now
+    let (reported_user_id, message_id, snapshot) = match body.target_type.as_str() {
+        "message" => {
+            let Some(message_id) = body.message_id else {
+                return HttpResponse::BadRequest().json(serde_json::json!({"error": "message_id is required"}));
+            };
+            let message = conn
+                .query_row(
+                    &format!("SELECT {} FROM direct_messages WHERE id = ?1", DM_COLUMNS),
+                    rusqlite::params![message_id],
+                    direct_message_from_row,
+                )
+                .ok()
+                .filter(|m| match_participants(&conn, m.match_id, &claims.sub).is_some() && m.sender_id != claims.sub);
+            let Some(mut message) = message else {
+                return HttpResponse::NotFound().json(serde_json::json!({"error": "Message not found"}));
+            };
+
+            // A deleted message still has its text in the row; the report keeps it
+            if message.is_deleted {
+                message.content = conn
+                    .query_row("SELECT content FROM direct_messages WHERE id = ?1", rusqlite::params![message_id], |row| row.get(0))
+                    .unwrap_or_default();
+            }
+            load_message_details(&conn, std::slice::from_mut(&mut message));
+            let edits: Vec<String> = conn
+                .prepare("SELECT previous_content FROM dm_edits WHERE message_id = ?1 ORDER BY id ASC")
+                .and_then(|mut stmt| stmt.query_map(rusqlite::params![message_id], |row| row.get(0))?.collect())
+                .unwrap_or_default();
+
+            let reported = message.sender_id.clone();
+            let snapshot = serde_json::json!({
+                "user": public_profile_snapshot(&conn, &reported),
+                "message": message,
+                "previous_versions": edits,
+            });
+            (reported, Some(message_id), snapshot)
+        }
+        target @ ("user" | "profile") => {
+            let Some(user_id) = body.user_id.clone() else {
+                return HttpResponse::BadRequest().json(serde_json::json!({"error": "user_id is required"}));
+            };
+            if user_id == claims.sub {
+                return HttpResponse::BadRequest().json(serde_json::json!({"error": "You cannot report yourself"}));
+            }
+            // You can only report people the app has introduced you to
+            if !shares_match(&conn, &claims.sub, &user_id) {
+                return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"}));
+            }
+
+            let mut snapshot = serde_json::json!({ "user": public_profile_snapshot(&conn, &user_id) });
+            if target == "user" {
+                snapshot["recent_messages"] = serde_json::json!(recent_messages_snapshot(&conn, &claims.sub, &user_id));
+            }
+            (user_id, None, snapshot)
+        }
+        _ => {
+            return HttpResponse::BadRequest().json(serde_json::json!({"error": "target_type must be 'user', 'message' or 'profile'"}));
+        }
+    };
+
+    // Reporting the same thing twice while the first report is still open adds nothing
+    let existing: Option<ReportSummary> = conn
+        .query_row(
+            "SELECT id, target_type, reported_user_id, message_id, reason, status, created_at FROM moderation_cases
+             WHERE reporter_id = ?1 AND reported_user_id = ?2 AND target_type = ?3 AND message_id IS ?4 AND status != 'closed'",
+            rusqlite::params![&claims.sub, &reported_user_id, &body.target_type, message_id],
+            report_summary_from_row,
+        )
+        .ok();
+    if let Some(existing) = existing {
+        return HttpResponse::Ok().json(existing);
+    }
+
+    // Threats and underage reports jump the queue
+    let priority = if body.reason == "threat" || body.reason == "underage" { "high" } else { "normal" };
+
+    let created = (|| -> rusqlite::Result<i64> {
+        conn.execute_batch("BEGIN")?;
+        let result = (|| {
+            conn.execute(
+                "INSERT INTO moderation_cases (reporter_id, reported_user_id, target_type, message_id, reason, details, snapshot, priority) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                rusqlite::params![&claims.sub, &reported_user_id, &body.target_type, message_id, &body.reason, &details, snapshot.to_string(), priority],
+            )?;
+            let case_id = conn.last_insert_rowid();
+            record_case_event(&conn, case_id, Some(&claims.sub), "reported", &body.reason)?;
+            Ok(case_id)
+        })();
+        match result {
+            Ok(id) => conn.execute_batch("COMMIT").map(|_| id),
+            Err(e) => {
+                let _ = conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    })();
+
+    let case_id = match created {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("Failed to file report from {}: {}", claims.sub, e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to file report"}));
+        }
+    };
+
+    log::info!("🚩 Case {} opened: {} reported {} for {}", case_id, claims.sub, reported_user_id, body.reason);
+
+    match conn.query_row(
+        "SELECT id, target_type, reported_user_id, message_id, reason, status, created_at FROM moderation_cases WHERE id = ?1",
+        rusqlite::params![case_id],
+        report_summary_from_row,
+    ) {
+        Ok(summary) => HttpResponse::Created().json(summary),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to file report"})),
+    }
+}
+
+pub async fn list_my_reports(
+    req: HttpRequest,
+    db: web::Data<Database>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let conn = db.conn.lock().unwrap();
+    let mut stmt = conn
+        .prepare("SELECT id, target_type, reported_user_id, message_id, reason, status, created_at FROM moderation_cases WHERE reporter_id = ?1 ORDER BY id DESC LIMIT 100")
+        .unwrap();
+    let reports: Vec<ReportSummary> = stmt
+        .query_map(rusqlite::params![&claims.sub], report_summary_from_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(reports)
+}
+
+fn report_summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<ReportSummary> {
+    Ok(ReportSummary {
+        id: row.get(0)?,
+        target_type: row.get(1)?,
+        reported_user_id: row.get(2)?,
+        message_id: row.get(3)?,
+        reason: row.get(4)?,
+        status: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
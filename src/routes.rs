@@ -1,31 +1,52 @@
 use actix_web::{web, HttpRequest, HttpResponse};
 use crate::agent::LlmAgent;
+use crate::attachments::{attachment_from_row, detach_message_attachments, remove_blobs, ATTACHMENT_COLUMNS};
 use crate::auth::extract_user_id;
+use crate::broker::Broker;
+use crate::icebreakers::generate_icebreakers_bg;
 use crate::db::Database;
+use crate::emoji::is_emoji;
 use crate::models::*;
+use crate::memory::{load_memory, recent_messages, summarize_bg, RECENT_MESSAGES};
+use crate::moderation::{record_flag, screen, ModerationContext, Moderator};
+use crate::pagination::PageRequest;
+use crate::onboarding::{self, get_preferences_db, mutually_acceptable};
+use crate::persona::get_persona_db;
+use crate::profile_history::{record_version, ProfileChange};
+use crate::profile_locks::{apply_locks, editable_profile};
+use crate::realtime::{match_participants, publish_to_match};
+use crate::storage::BlobStore;
+use crate::visibility::{known_names_db, profile_field, profile_for, Audience, PROFILE_FIELDS};
 
 // ── Chat with personal agent ──
 
 pub async fn get_chat_history(
     req: HttpRequest,
     db: web::Data<Database>,
+    query: web::Query<PageQuery>,
 ) -> HttpResponse {
     let claims = match extract_user_id(&req) {
         Ok(c) => c,
         Err(e) => return e,
     };
 
+    let page = match PageRequest::from_query(&query, 50, 200) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+
     let conn = db.conn.lock().unwrap();
     let mut stmt = conn
-        .prepare("SELECT id, role, content, created_at FROM conversations WHERE user_id = ?1 ORDER BY created_at ASC")
+        .prepare(&format!("SELECT id, role, content, created_at, prompt_version FROM conversations WHERE user_id = ? AND {}", page.clause("id")))
         .unwrap();
 
     let messages: Vec<ChatMessage> = stmt
-        .query_map(rusqlite::params![&claims.sub], |row| {
+        .query_map(rusqlite::params![&claims.sub, page.bound(), page.fetch_limit()], |row| {
             Ok(ChatMessage {
                 id: Some(row.get(0)?),
                 role: row.get(1)?,
                 content: row.get(2)?,
+                prompt_version: row.get(4)?,
                 created_at: Some(row.get(3)?),
             })
         })
@@ -33,13 +54,14 @@ pub async fn get_chat_history(
         .filter_map(|r| r.ok())
         .collect();
 
-    HttpResponse::Ok().json(messages)
+    HttpResponse::Ok().json(page.paginate(messages, |m| m.id.unwrap_or_default(), true))
 }
 
 pub async fn send_message(
     req: HttpRequest,
     db: web::Data<Database>,
     agent: web::Data<LlmAgent>,
+    moderator: web::Data<dyn Moderator>,
     body: web::Json<SendMessageRequest>,
 ) -> HttpResponse {
     let claims = match extract_user_id(&req) {
@@ -54,29 +76,32 @@ pub async fn send_message(
         return HttpResponse::BadRequest().json(serde_json::json!({"error": "Message cannot be empty"}));
     }
 
-    // Get conversation history
-    let history = {
+    let verdict = match screen(moderator.get_ref(), &db, &user_id, ModerationContext::AgentChat, None, &user_content).await {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
+
+    // The latest turns verbatim, plus a summary and recall for everything before them
+    let (history, memory, total_messages) = {
         let conn = db.conn.lock().unwrap();
-        let mut stmt = conn
-            .prepare("SELECT id, role, content, created_at FROM conversations WHERE user_id = ?1 ORDER BY created_at ASC LIMIT 50")
-            .unwrap();
-        stmt.query_map(rusqlite::params![&user_id], |row| {
-            Ok(ChatMessage {
-                id: Some(row.get(0)?),
-                role: row.get(1)?,
-                content: row.get(2)?,
-                created_at: Some(row.get(3)?),
-            })
-        })
-        .unwrap()
-        .filter_map(|r| r.ok())
-        .collect::<Vec<_>>()
+        let history = recent_messages(&conn, &user_id, RECENT_MESSAGES);
+        let oldest_recent = history.first().and_then(|m| m.id).unwrap_or(i64::MAX);
+        let memory = load_memory(&conn, &user_id, &user_content, oldest_recent);
+        let total: i64 = conn
+            .query_row("SELECT COUNT(*) FROM conversations WHERE user_id = ?1", rusqlite::params![&user_id], |row| row.get(0))
+            .unwrap_or(0);
+        (history, memory, total)
     };
 
-    // Get agent profile
-    let agent_profile = {
+    // Get agent profile, the persona it speaks with and what onboarding already covered
+    let (agent_profile, persona, onboarding, known_names) = {
         let conn = db.conn.lock().unwrap();
-        get_agent_profile_db(&conn, &user_id)
+        (
+            get_agent_profile_db(&conn, &user_id),
+            get_persona_db(&conn, &user_id),
+            onboarding::chat_context(&conn, &user_id),
+            known_names_db(&conn, &user_id),
+        )
     };
 
     // Save user message
@@ -86,14 +111,15 @@ pub async fn send_message(
             "INSERT INTO conversations (user_id, role, content) VALUES (?1, 'user', ?2)",
             rusqlite::params![&user_id, &user_content],
         ).unwrap();
+        record_flag(&conn, &user_id, ModerationContext::AgentChat, None, Some(conn.last_insert_rowid()), &user_content, &verdict);
     }
 
     // Get LLM response
-    let agent_response = match agent.chat_with_user(&history, &agent_profile, &user_content).await {
-        Ok(r) => r,
+    let (agent_response, prompt_version) = match agent.chat_with_user(&history, &agent_profile, &persona, &onboarding, &memory, &user_content, &known_names).await {
+        Ok((reply, version)) => (reply, Some(version)),
         Err(e) => {
             log::error!("Agent chat error: {}", e);
-            format!("I'm having a moment — could you try again? (Error: {})", e)
+            (format!("I'm having a moment — could you try again? (Error: {})", e), None)
         }
     };
 
@@ -101,13 +127,13 @@ pub async fn send_message(
     {
         let conn = db.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO conversations (user_id, role, content) VALUES (?1, 'assistant', ?2)",
-            rusqlite::params![&user_id, &agent_response],
+            "INSERT INTO conversations (user_id, role, content, prompt_version) VALUES (?1, 'assistant', ?2, ?3)",
+            rusqlite::params![&user_id, &agent_response, &prompt_version],
         ).unwrap();
     }
 
     // Trigger profile update in background (every 5 messages)
-    let msg_count = history.len() + 2; // +2 for new messages
+    let msg_count = total_messages + 2; // +2 for new messages
     if msg_count % 5 == 0 {
         let db_clone = db.clone();
         let agent_clone = agent.clone();
@@ -117,10 +143,21 @@ pub async fn send_message(
         });
     }
 
+    // Keep the long-term summary rolling as messages leave the recent window
+    {
+        let db_clone = db.clone();
+        let agent_clone = agent.clone();
+        let uid = user_id.clone();
+        tokio::spawn(async move {
+            summarize_bg(db_clone, agent_clone, uid).await;
+        });
+    }
+
     let user_msg = ChatMessage {
         id: None,
         role: "user".to_string(),
         content: user_content,
+        prompt_version: None,
         created_at: Some(chrono::Utc::now().to_rfc3339()),
     };
 
@@ -128,6 +165,7 @@ pub async fn send_message(
         id: None,
         role: "assistant".to_string(),
         content: agent_response,
+        prompt_version,
         created_at: Some(chrono::Utc::now().to_rfc3339()),
     };
 
@@ -137,50 +175,45 @@ pub async fn send_message(
     })
 }
 
-async fn update_user_profile_bg(
+pub async fn update_user_profile_bg(
     db: web::Data<Database>,
     agent: web::Data<LlmAgent>,
     user_id: String,
 ) {
     let history = {
         let conn = db.conn.lock().unwrap();
-        let mut stmt = conn
-            .prepare("SELECT id, role, content, created_at FROM conversations WHERE user_id = ?1 ORDER BY created_at DESC LIMIT 30")
-            .unwrap();
-        stmt.query_map(rusqlite::params![&user_id], |row| {
-            Ok(ChatMessage {
-                id: Some(row.get(0)?),
-                role: row.get(1)?,
-                content: row.get(2)?,
-                created_at: Some(row.get(3)?),
-            })
-        })
-        .unwrap()
-        .filter_map(|r| r.ok())
-        .collect::<Vec<_>>()
+        recent_messages(&conn, &user_id, 30)
     };
 
-    let current_profile = {
+    let (current_profile, known_names) = {
         let conn = db.conn.lock().unwrap();
-        get_agent_profile_db(&conn, &user_id)
+        (get_agent_profile_db(&conn, &user_id), known_names_db(&conn, &user_id))
     };
 
-    match agent.update_user_profile(&history, &current_profile).await {
-        Ok(updated) => {
+    let feedback = {
+        let conn = db.conn.lock().unwrap();
+        recent_feedback(&conn, &user_id)
+    };
+
+    // The messages this revision was built from, recorded with it
+    let ids = history.iter().filter_map(|m| m.id);
+    let conversation_range = ids.clone().min().zip(ids.max());
+
+    match agent.update_user_profile(&history, &current_profile, &feedback, &known_names).await {
+        Ok((updated, prompt_version)) => {
             let conn = db.conn.lock().unwrap();
-            let _ = conn.execute(
-                "UPDATE agent_profiles SET personality_summary=?1, interests=?2, core_values=?3, communication_style=?4, looking_for=?5, deal_breakers=?6, raw_notes=?7, updated_at=datetime('now') WHERE user_id=?8",
-                rusqlite::params![
-                    &updated.personality_summary,
-                    &updated.interests,
-                    &updated.core_values,
-                    &updated.communication_style,
-                    &updated.looking_for,
-                    &updated.deal_breakers,
-                    &updated.raw_notes,
-                    &user_id,
-                ],
-            );
+            // Remembered so the now-unredacted profile can be shown to other agents without them
+            for name in agent.introduced_names(&history) {
+                conn.execute(
+                    "INSERT OR IGNORE INTO known_names (user_id, name) VALUES (?1, ?2)",
+                    rusqlite::params![&user_id, &name],
+                ).unwrap();
+            }
+            // Locked fields keep the user's value; the agent's version becomes a proposal
+            let updated = apply_locks(&conn, &user_id, updated);
+            if let Err(e) = save_agent_profile_db(&conn, &user_id, &updated, ProfileChange::Agent { conversation_range, prompt_version }) {
+                log::error!("Failed to save profile for {}: {}", user_id, e);
+            }
             log::info!("Updated profile for user {}", user_id);
         }
         Err(e) => log::error!("Profile update failed for {}: {}", user_id, e),
@@ -199,8 +232,7 @@ pub async fn get_agent_profile(
     };
 
     let conn = db.conn.lock().unwrap();
-    let profile = get_agent_profile_db(&conn, &claims.sub);
-    HttpResponse::Ok().json(profile)
+    HttpResponse::Ok().json(editable_profile(&conn, &claims.sub))
 }
 
 pub async fn trigger_profile_update(
@@ -223,7 +255,7 @@ pub async fn trigger_profile_update(
     HttpResponse::Ok().json(serde_json::json!({"status": "Profile update triggered"}))
 }
 
-fn get_agent_profile_db(conn: &rusqlite::Connection, user_id: &str) -> AgentProfile {
+pub fn get_agent_profile_db(conn: &rusqlite::Connection, user_id: &str) -> AgentProfile {
     conn.query_row(
         "SELECT user_id, personality_summary, interests, core_values, communication_style, looking_for, deal_breakers, raw_notes, updated_at FROM agent_profiles WHERE user_id = ?1",
         rusqlite::params![user_id],
@@ -244,37 +276,79 @@ fn get_agent_profile_db(conn: &rusqlite::Connection, user_id: &str) -> AgentProf
     .unwrap_or_default()
 }
 
+/// Overwrite a user's profile and record the revision. Saving an unchanged profile is a no-op.
+pub fn save_agent_profile_db(
+    conn: &rusqlite::Connection,
+    user_id: &str,
+    profile: &AgentProfile,
+    change: ProfileChange,
+) -> rusqlite::Result<()> {
+    let previous = get_agent_profile_db(conn, user_id);
+    if PROFILE_FIELDS.iter().all(|f| profile_field(&previous, f) == profile_field(profile, f)) {
+        return Ok(());
+    }
+
+    conn.execute(
+        "UPDATE agent_profiles SET personality_summary=?1, interests=?2, core_values=?3, communication_style=?4, looking_for=?5, deal_breakers=?6, raw_notes=?7, updated_at=datetime('now') WHERE user_id=?8",
+        rusqlite::params![
+            &profile.personality_summary,
+            &profile.interests,
+            &profile.core_values,
+            &profile.communication_style,
+            &profile.looking_for,
+            &profile.deal_breakers,
+            &profile.raw_notes,
+            user_id,
+        ],
+    )?;
+    record_version(conn, user_id, &previous, profile, &change)
+}
+
 // ── Matching Engine ──
 
 pub async fn trigger_matching(
     req: HttpRequest,
     db: web::Data<Database>,
     agent: web::Data<LlmAgent>,
+    broker: web::Data<dyn Broker>,
 ) -> HttpResponse {
     let claims = match extract_user_id(&req) {
         Ok(c) => c,
         Err(e) => return e,
     };
 
-    let my_user_id = claims.sub.clone();
+    match run_matching(&db, &agent, broker.get_ref(), &claims.sub).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({"error": e})),
+    }
+}
 
-    // Get my profile
-    let my_profile = {
+/// Let a user's agent evaluate every other profiled user and record proposals and mutual matches
+pub async fn run_matching(
+    db: &web::Data<Database>,
+    agent: &web::Data<LlmAgent>,
+    broker: &dyn Broker,
+    user_id: &str,
+) -> Result<MatchingStatus, String> {
+    let my_user_id = user_id.to_string();
+
+    // Get my profile, minus anything I've kept private
+    let (my_profile, my_names) = {
         let conn = db.conn.lock().unwrap();
-        get_agent_profile_db(&conn, &my_user_id)
+        let full = get_agent_profile_db(&conn, &my_user_id);
+        if full.personality_summary.is_empty() && full.interests.is_empty() {
+            return Err("Your agent doesn't know enough about you yet. Chat more first!".to_string());
+        }
+        (profile_for(&conn, &my_user_id, Audience::OwnAgent), known_names_db(&conn, &my_user_id))
     };
 
-    if my_profile.personality_summary.is_empty() && my_profile.interests.is_empty() {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Your agent doesn't know enough about you yet. Chat more first!"
-        }));
-    }
-
-    // Get all other users with profiles
+    // Get all other users with profiles who pass both sides' onboarding hard filters,
+    // so nobody is sent to the LLM only to be ruled out by age, gender or city
     let other_users: Vec<(String, AgentProfile)> = {
         let conn = db.conn.lock().unwrap();
+        let my_prefs = get_preferences_db(&conn, &my_user_id);
         let mut stmt = conn
-            .prepare("SELECT user_id FROM agent_profiles WHERE user_id != ?1 AND (personality_summary != '' OR interests != '')")
+            .prepare("SELECT user_id FROM agent_profiles WHERE user_id != ?1 AND (personality_summary != '' OR interests != '') AND user_id IN (SELECT id FROM users WHERE deleted_at IS NULL AND deletion_requested_at IS NULL AND suspended_at IS NULL)")
             .unwrap();
         stmt.query_map(rusqlite::params![&my_user_id], |row| {
             let uid: String = row.get(0)?;
@@ -282,23 +356,30 @@ pub async fn trigger_matching(
         })
         .unwrap()
         .filter_map(|r| r.ok())
+        .filter(|uid| mutually_acceptable(&my_prefs, &get_preferences_db(&conn, uid)))
         .map(|uid| {
-            let profile = get_agent_profile_db(&conn, &uid);
+            // Other agents only ever see what each user marked shareable
+            let profile = profile_for(&conn, &uid, Audience::OtherAgent);
             (uid, profile)
         })
         .collect()
     };
 
+    let feedback = {
+        let conn = db.conn.lock().unwrap();
+        recent_feedback(&conn, &my_user_id)
+    };
+
     let mut evaluated = 0;
     let mut new_recommendations = 0;
     let mut new_matches = 0;
 
     for (other_id, other_profile) in &other_users {
-        // Get existing notes
-        let existing_notes: Option<AgentPeerNote> = {
+        // Get existing notes, and both users' names so neither reaches the LLM
+        let (existing_notes, known_names) = {
             let conn = db.conn.lock().unwrap();
-            conn.query_row(
-                "SELECT id, agent_user_id, about_user_id, compatibility_score, notes, recommends_match, conversation_count, updated_at FROM agent_peer_notes WHERE agent_user_id = ?1 AND about_user_id = ?2",
+            let existing_notes: Option<AgentPeerNote> = conn.query_row(
+                "SELECT id, agent_user_id, about_user_id, compatibility_score, notes, recommends_match, conversation_count, updated_at, prompt_version FROM agent_peer_notes WHERE agent_user_id = ?1 AND about_user_id = ?2",
                 rusqlite::params![&my_user_id, other_id],
                 |row| {
                     Ok(AgentPeerNote {
@@ -309,19 +390,23 @@ pub async fn trigger_matching(
                         notes: row.get(4)?,
                         recommends_match: row.get::<_, i32>(5)? != 0,
                         conversation_count: row.get(6)?,
+                        prompt_version: row.get(8)?,
                         updated_at: row.get(7)?,
                     })
                 },
             )
-            .ok()
+            .ok();
+            let mut known_names = my_names.clone();
+            known_names.extend(known_names_db(&conn, other_id));
+            (existing_notes, known_names)
         };
 
         // Evaluate compatibility
         match agent
-            .evaluate_compatibility(&my_profile, other_profile, existing_notes.as_ref())
+            .evaluate_compatibility(&my_profile, other_profile, existing_notes.as_ref(), &feedback, &known_names)
             .await
         {
-            Ok((score, notes, recommends)) => {
+            Ok((score, notes, recommends, prompt_version)) => {
                 evaluated += 1;
                 let conv_count = existing_notes
                     .as_ref()
@@ -332,11 +417,11 @@ pub async fn trigger_matching(
                 {
                     let conn = db.conn.lock().unwrap();
                     conn.execute(
-                        "INSERT INTO agent_peer_notes (agent_user_id, about_user_id, compatibility_score, notes, recommends_match, conversation_count, updated_at)
-                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))
+                        "INSERT INTO agent_peer_notes (agent_user_id, about_user_id, compatibility_score, notes, recommends_match, conversation_count, prompt_version, updated_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime('now'))
                          ON CONFLICT(agent_user_id, about_user_id) DO UPDATE SET
-                         compatibility_score=?3, notes=?4, recommends_match=?5, conversation_count=?6, updated_at=datetime('now')",
-                        rusqlite::params![&my_user_id, other_id, score, &notes, recommends as i32, conv_count],
+                         compatibility_score=?3, notes=?4, recommends_match=?5, conversation_count=?6, prompt_version=?7, updated_at=datetime('now')",
+                        rusqlite::params![&my_user_id, other_id, score, &notes, recommends as i32, conv_count, &prompt_version],
                     ).unwrap();
                 }
 
@@ -368,17 +453,24 @@ pub async fn trigger_matching(
                             |row| row.get(0),
                         ).unwrap_or_else(|_| "Someone".to_string());
 
-                        conn.execute(
-                            "INSERT INTO notifications (user_id, notification_type, title, message, related_user_id) VALUES (?1, 'match_proposal', 'New Match Suggestion!', ?2, ?3)",
-                            rusqlite::params![
-                                other_id,
-                                format!("Your agent has been contacted by {}'s agent. They think you might be a great match! (Compatibility: {:.0}%)", my_name, score * 100.0),
-                                &my_user_id,
-                            ],
+                        create_notification(
+                            &conn,
+                            broker,
+                            other_id,
+                            "match_proposal",
+                            "New Match Suggestion!",
+                            &format!("Your agent has been contacted by {}'s agent. They think you might be a great match! (Compatibility: {:.0}%)", my_name, score * 100.0),
+                            Some(&my_user_id),
                         ).unwrap();
                     } else {
                         // Check if other agent already approved — if so, it's a mutual match!
                         let conn = db.conn.lock().unwrap();
+                        let was_matched: bool = conn.query_row(
+                            "SELECT is_matched FROM matches WHERE (user_a_id=?1 AND user_b_id=?2) OR (user_a_id=?2 AND user_b_id=?1)",
+                            rusqlite::params![&my_user_id, other_id],
+                            |row| row.get::<_, i32>(0),
+                        ).unwrap_or(0) != 0;
+
                         let updated = conn.execute(
                             "UPDATE matches SET agent_a_approves = 1, is_matched = CASE WHEN agent_b_approves = 1 THEN 1 ELSE is_matched END, updated_at = datetime('now') WHERE user_a_id = ?1 AND user_b_id = ?2",
                             rusqlite::params![&my_user_id, other_id],
@@ -392,15 +484,22 @@ pub async fn trigger_matching(
                             ).unwrap();
                         }
 
-                        // Check if now matched
-                        let is_matched: bool = conn.query_row(
-                            "SELECT is_matched FROM matches WHERE (user_a_id=?1 AND user_b_id=?2) OR (user_a_id=?2 AND user_b_id=?1)",
+                        // Check if this run just confirmed the match
+                        let (match_id, is_matched): (i64, bool) = conn.query_row(
+                            "SELECT id, is_matched FROM matches WHERE (user_a_id=?1 AND user_b_id=?2) OR (user_a_id=?2 AND user_b_id=?1)",
                             rusqlite::params![&my_user_id, other_id],
-                            |row| row.get::<_, i32>(0),
-                        ).unwrap_or(0) != 0;
+                            |row| Ok((row.get(0)?, row.get::<_, i32>(1)? != 0)),
+                        ).unwrap_or((0, false));
 
-                        if is_matched {
+                        if is_matched && !was_matched {
                             new_matches += 1;
+
+                            let db_clone = db.clone();
+                            let agent_clone = agent.clone();
+                            tokio::spawn(async move {
+                                generate_icebreakers_bg(db_clone, agent_clone, match_id).await;
+                            });
+
                             // Notify both users
                             let my_name: String = conn.query_row(
                                 "SELECT display_name FROM users WHERE id = ?1",
@@ -414,22 +513,24 @@ pub async fn trigger_matching(
                                 |row| row.get(0),
                             ).unwrap_or_else(|_| "Someone".to_string());
 
-                            conn.execute(
-                                "INSERT INTO notifications (user_id, notification_type, title, message, related_user_id) VALUES (?1, 'match_confirmed', '🎉 It''s a Match!', ?2, ?3)",
-                                rusqlite::params![
-                                    &my_user_id,
-                                    format!("Both agents agree — you and {} could be amazing together! You can now chat directly.", other_name),
-                                    other_id,
-                                ],
+                            create_notification(
+                                &conn,
+                                broker,
+                                &my_user_id,
+                                "match_confirmed",
+                                "🎉 It's a Match!",
+                                &format!("Both agents agree — you and {} could be amazing together! You can now chat directly.", other_name),
+                                Some(other_id),
                             ).unwrap();
 
-                            conn.execute(
-                                "INSERT INTO notifications (user_id, notification_type, title, message, related_user_id) VALUES (?1, 'match_confirmed', '🎉 It''s a Match!', ?2, ?3)",
-                                rusqlite::params![
-                                    other_id,
-                                    format!("Both agents agree — you and {} could be amazing together! You can now chat directly.", my_name),
-                                    &my_user_id,
-                                ],
+                            create_notification(
+                                &conn,
+                                broker,
+                                other_id,
+                                "match_confirmed",
+                                "🎉 It's a Match!",
+                                &format!("Both agents agree — you and {} could be amazing together! You can now chat directly.", my_name),
+                                Some(&my_user_id),
                             ).unwrap();
                         }
                     }
@@ -441,7 +542,7 @@ pub async fn trigger_matching(
         }
     }
 
-    HttpResponse::Ok().json(MatchingStatus {
+    Ok(MatchingStatus {
         evaluated,
         new_recommendations,
         new_matches,
@@ -453,45 +554,66 @@ pub async fn trigger_matching(
 pub async fn get_matches(
     req: HttpRequest,
     db: web::Data<Database>,
+    query: web::Query<PageQuery>,
 ) -> HttpResponse {
     let claims = match extract_user_id(&req) {
         Ok(c) => c,
         Err(e) => return e,
     };
 
+    let page = match PageRequest::from_query(&query, 50, 200) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+
     let conn = db.conn.lock().unwrap();
     let mut stmt = conn
-        .prepare(
-            "SELECT m.id, m.user_a_id, m.user_b_id, m.agent_a_approves, m.agent_b_approves, m.is_matched, m.created_at, m.updated_at
+        .prepare(&format!(
+            "SELECT m.id, m.user_a_id, m.user_b_id, m.agent_a_approves, m.agent_b_approves, m.is_matched, m.created_at, m.updated_at,
+                    u.id, u.username, u.email, u.display_name, u.bio, u.created_at
              FROM matches m
-             WHERE m.user_a_id = ?1 OR m.user_b_id = ?1
-             ORDER BY m.is_matched DESC, m.updated_at DESC"
-        )
+             LEFT JOIN users u ON u.id = CASE WHEN m.user_a_id = ? THEN m.user_b_id ELSE m.user_a_id END
+             WHERE (m.user_a_id = ? OR m.user_b_id = ?) AND {}",
+            page.clause("m.id")
+        ))
         .unwrap();
 
     let matches: Vec<MatchRecord> = stmt
-        .query_map(rusqlite::params![&claims.sub], |row| {
+        .query_map(rusqlite::params![&claims.sub, &claims.sub, &claims.sub, page.bound(), page.fetch_limit()], |row| {
             let user_a_id: String = row.get(1)?;
             let user_b_id: String = row.get(2)?;
             let other_user_id = if user_a_id == claims.sub { &user_b_id } else { &user_a_id };
 
-            let other_user = conn.query_row(
-                "SELECT id, username, email, display_name, bio, created_at FROM users WHERE id = ?1",
-                rusqlite::params![other_user_id],
-                |r| {
-                    Ok(UserPublic {
-                        id: r.get(0)?,
-                        username: r.get(1)?,
-                        email: r.get(2)?,
-                        display_name: r.get(3)?,
-                        bio: r.get(4)?,
-                        created_at: r.get(5)?,
-                    })
-                },
+            let other_user = match row.get::<_, Option<String>>(8)? {
+                Some(id) => Some(UserPublic {
+                    id,
+                    username: row.get(9)?,
+                    email: row.get(10)?,
+                    display_name: row.get(11)?,
+                    bio: row.get(12)?,
+                    created_at: row.get(13)?,
+                }),
+                None => None,
+            };
+
+            let match_id: i64 = row.get(0)?;
+            let my_last_read = last_read_message_id(&conn, match_id, &claims.sub);
+            // Deleted messages neither count as unread nor stand in as the preview
+            let unread_count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM direct_messages WHERE match_id = ?1 AND sender_id != ?2 AND id > ?3 AND deleted_at IS NULL",
+                rusqlite::params![match_id, &claims.sub, my_last_read],
+                |r| r.get(0),
+            ).unwrap_or(0);
+
+            let last_message = conn.query_row(
+                &format!("SELECT {} FROM direct_messages WHERE match_id = ?1 AND deleted_at IS NULL ORDER BY id DESC LIMIT 1", DM_COLUMNS),
+                rusqlite::params![match_id],
+                direct_message_from_row,
             ).ok();
 
             Ok(MatchRecord {
-                id: row.get(0)?,
+                id: match_id,
+                other_last_read_message_id: last_read_message_id(&conn, match_id, other_user_id),
                 user_a_id,
                 user_b_id,
                 agent_a_approves: row.get::<_, i32>(3)? != 0,
@@ -500,33 +622,191 @@ pub async fn get_matches(
                 created_at: row.get(6)?,
                 updated_at: row.get(7)?,
                 other_user,
+                unread_count,
+                last_message,
             })
         })
         .unwrap()
         .filter_map(|r| r.ok())
         .collect();
 
-    HttpResponse::Ok().json(matches)
+    HttpResponse::Ok().json(page.paginate(matches, |m| m.id, false))
+}
+
+/// The user's latest match feedback, paired with their agent's notes on each match
+pub fn recent_feedback(conn: &rusqlite::Connection, user_id: &str) -> Vec<FeedbackEvidence> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.rating, f.met_in_person, f.comment, COALESCE(n.notes, '')
+             FROM match_feedback f
+             JOIN matches m ON m.id = f.match_id
+             LEFT JOIN agent_peer_notes n ON n.agent_user_id = f.user_id
+                 AND n.about_user_id = CASE WHEN m.user_a_id = f.user_id THEN m.user_b_id ELSE m.user_a_id END
+             WHERE f.user_id = ?1
+             ORDER BY f.id DESC LIMIT 10",
+        )
+        .unwrap();
+    stmt.query_map(rusqlite::params![user_id], |row| {
+        Ok(FeedbackEvidence {
+            rating: row.get(0)?,
+            met_in_person: row.get::<_, i32>(1)? != 0,
+            comment: row.get(2)?,
+            agent_notes: row.get(3)?,
+        })
+    })
+    .unwrap()
+    .filter_map(|r| r.ok())
+    .collect()
+}
+
+pub async fn submit_match_feedback(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    agent: web::Data<LlmAgent>,
+    path: web::Path<i64>,
+    body: web::Json<MatchFeedbackRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    if !(1..=5).contains(&body.rating) {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "rating must be between 1 and 5"}));
+    }
+
+    let comment = body.comment.clone().unwrap_or_default().trim().to_string();
+    if comment.chars().count() > 2000 {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "comment must be at most 2000 characters"}));
+    }
+
+    let match_id = path.into_inner();
+    let feedback = {
+        let conn = db.conn.lock().unwrap();
+        if match_participants(&conn, match_id, &claims.sub).is_none() {
+            return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}));
+        }
+
+        let met_in_person = body.met_in_person.unwrap_or(false);
+        conn.execute(
+            "INSERT INTO match_feedback (match_id, user_id, rating, met_in_person, comment) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![match_id, &claims.sub, body.rating, met_in_person as i32, &comment],
+        ).unwrap();
+
+        MatchFeedback {
+            id: conn.last_insert_rowid(),
+            match_id,
+            rating: body.rating,
+            met_in_person,
+            comment,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    };
+
+    // Feedback is private to its author; it only teaches their own agent
+    let db_clone = db.clone();
+    let agent_clone = agent.clone();
+    let user_id = claims.sub.clone();
+    tokio::spawn(async move {
+        update_user_profile_bg(db_clone, agent_clone, user_id).await;
+    });
+
+    HttpResponse::Ok().json(feedback)
+}
+
+pub async fn get_match_feedback(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let match_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    if match_participants(&conn, match_id, &claims.sub).is_none() {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}));
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT id, match_id, rating, met_in_person, comment, created_at FROM match_feedback WHERE match_id = ?1 AND user_id = ?2 ORDER BY id ASC")
+        .unwrap();
+    let feedback: Vec<MatchFeedback> = stmt
+        .query_map(rusqlite::params![match_id, &claims.sub], match_feedback_from_row)
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(feedback)
+}
+
+pub fn match_feedback_from_row(row: &rusqlite::Row) -> rusqlite::Result<MatchFeedback> {
+    Ok(MatchFeedback {
+        id: row.get(0)?,
+        match_id: row.get(1)?,
+        rating: row.get(2)?,
+        met_in_person: row.get::<_, i32>(3)? != 0,
+        comment: row.get(4)?,
+        created_at: row.get(5)?,
+    })
 }
 
 // ── Notifications ──
 
+/// Store a notification and push it to the user's live streams
+pub fn create_notification(
+    conn: &rusqlite::Connection,
+    broker: &dyn Broker,
+    user_id: &str,
+    notification_type: &str,
+    title: &str,
+    message: &str,
+    related_user_id: Option<&str>,
+) -> rusqlite::Result<Notification> {
+    conn.execute(
+        "INSERT INTO notifications (user_id, notification_type, title, message, related_user_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![user_id, notification_type, title, message, related_user_id],
+    )?;
+
+    let notification = Notification {
+        id: conn.last_insert_rowid(),
+        user_id: user_id.to_string(),
+        notification_type: notification_type.to_string(),
+        title: title.to_string(),
+        message: message.to_string(),
+        related_user_id: related_user_id.map(str::to_string),
+        is_read: false,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    broker.publish(user_id, RealtimeEvent::Notification { notification: notification.clone() });
+    Ok(notification)
+}
+
 pub async fn get_notifications(
     req: HttpRequest,
     db: web::Data<Database>,
+    query: web::Query<PageQuery>,
 ) -> HttpResponse {
     let claims = match extract_user_id(&req) {
         Ok(c) => c,
         Err(e) => return e,
     };
 
+    let page = match PageRequest::from_query(&query, 50, 200) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+
     let conn = db.conn.lock().unwrap();
     let mut stmt = conn
-        .prepare("SELECT id, user_id, notification_type, title, message, related_user_id, is_read, created_at FROM notifications WHERE user_id = ?1 ORDER BY created_at DESC LIMIT 50")
+        .prepare(&format!("SELECT id, user_id, notification_type, title, message, related_user_id, is_read, created_at FROM notifications WHERE user_id = ? AND {}", page.clause("id")))
         .unwrap();
 
     let notifications: Vec<Notification> = stmt
-        .query_map(rusqlite::params![&claims.sub], |row| {
+        .query_map(rusqlite::params![&claims.sub, page.bound(), page.fetch_limit()], |row| {
             Ok(Notification {
                 id: row.get(0)?,
                 user_id: row.get(1)?,
@@ -542,7 +822,7 @@ pub async fn get_notifications(
         .filter_map(|r| r.ok())
         .collect();
 
-    HttpResponse::Ok().json(notifications)
+    HttpResponse::Ok().json(page.paginate(notifications, |n| n.id, false))
 }
 
 pub async fn mark_notification_read(
@@ -592,6 +872,7 @@ pub async fn get_direct_messages(
     req: HttpRequest,
     db: web::Data<Database>,
     path: web::Path<i64>,
+    query: web::Query<PageQuery>,
 ) -> HttpResponse {
     let claims = match extract_user_id(&req) {
         Ok(c) => c,
@@ -599,6 +880,10 @@ pub async fn get_direct_messages(
     };
 
     let match_id = path.into_inner();
+    let page = match PageRequest::from_query(&query, 50, 200) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
 
     // Verify user is part of this match and it's confirmed
     let conn = db.conn.lock().unwrap();
@@ -616,29 +901,151 @@ pub async fn get_direct_messages(
     }
 
     let mut stmt = conn
-        .prepare("SELECT id, match_id, sender_id, content, created_at FROM direct_messages WHERE match_id = ?1 ORDER BY created_at ASC")
+        .prepare(&format!("SELECT {} FROM direct_messages WHERE match_id = ? AND {}", DM_COLUMNS, page.clause("id")))
         .unwrap();
 
-    let messages: Vec<DirectMessage> = stmt
-        .query_map(rusqlite::params![match_id], |row| {
-            Ok(DirectMessage {
-                id: row.get(0)?,
-                match_id: row.get(1)?,
-                sender_id: row.get(2)?,
-                content: row.get(3)?,
-                created_at: row.get(4)?,
-            })
-        })
+    let mut messages: Vec<DirectMessage> = stmt
+        .query_map(rusqlite::params![match_id, page.bound(), page.fetch_limit()], direct_message_from_row)
         .unwrap()
         .filter_map(|r| r.ok())
         .collect();
+    load_message_details(&conn, &mut messages);
+
+    HttpResponse::Ok().json(page.paginate(messages, |m| m.id, true))
+}
+
+pub const DM_COLUMNS: &str = "id, match_id, sender_id, content, created_at, edited_at, deleted_at";
+
+/// Map a row selected with `DM_COLUMNS`. Deleted messages keep their row but lose their content.
+pub fn direct_message_from_row(row: &rusqlite::Row) -> rusqlite::Result<DirectMessage> {
+    let deleted_at: Option<String> = row.get(6)?;
+    Ok(DirectMessage {
+        id: row.get(0)?,
+        match_id: row.get(1)?,
+        sender_id: row.get(2)?,
+        content: if deleted_at.is_some() { String::new() } else { row.get(3)? },
+        created_at: row.get(4)?,
+        edited_at: row.get(5)?,
+        is_deleted: deleted_at.is_some(),
+        deleted_at,
+        reactions: Vec::new(),
+        attachments: Vec::new(),
+    })
+}
+
+/// Fill in `reactions` and `attachments` for a batch of messages
+pub fn load_message_details(conn: &rusqlite::Connection, messages: &mut [DirectMessage]) {
+    let mut reactions_stmt = conn
+        .prepare("SELECT user_id, emoji, created_at FROM dm_reactions WHERE message_id = ?1 ORDER BY created_at ASC")
+        .unwrap();
+    let mut attachments_stmt = conn
+        .prepare(&format!("SELECT {} FROM dm_attachments WHERE message_id = ?1 ORDER BY created_at ASC", ATTACHMENT_COLUMNS))
+        .unwrap();
+    for msg in messages.iter_mut().filter(|m| !m.is_deleted) {
+        msg.reactions = reactions_stmt
+            .query_map(rusqlite::params![msg.id], |row| {
+                Ok(DmReaction {
+                    user_id: row.get(0)?,
+                    emoji: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            })
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+        msg.attachments = attachments_stmt
+            .query_map(rusqlite::params![msg.id], attachment_from_row)
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+    }
+}
+
+pub fn get_direct_message_db(conn: &rusqlite::Connection, match_id: i64, message_id: i64) -> Option<DirectMessage> {
+    let mut msg = conn
+        .query_row(
+            &format!("SELECT {} FROM direct_messages WHERE id = ?1 AND match_id = ?2", DM_COLUMNS),
+            rusqlite::params![message_id, match_id],
+            direct_message_from_row,
+        )
+        .ok()?;
+    load_message_details(conn, std::slice::from_mut(&mut msg));
+    Some(msg)
+}
+
+fn dm_edit_window_minutes() -> i64 {
+    std::env::var("DM_EDIT_WINDOW_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(15)
+}
+
+fn last_read_message_id(conn: &rusqlite::Connection, match_id: i64, user_id: &str) -> i64 {
+    conn.query_row(
+        "SELECT last_read_message_id FROM dm_read_state WHERE match_id = ?1 AND user_id = ?2",
+        rusqlite::params![match_id, user_id],
+        |row| row.get(0),
+    )
+    .unwrap_or(0)
+}
+
+pub async fn mark_messages_read(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    path: web::Path<i64>,
+    body: Option<web::Json<MarkReadRequest>>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let match_id = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+
+    let participants = match match_participants(&conn, match_id, &claims.sub) {
+        Some(p) => p,
+        None => return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"})),
+    };
 
-    HttpResponse::Ok().json(messages)
+    // Default to everything up to the latest message; never accept ids from other threads
+    let requested = body.and_then(|b| b.message_id);
+    let latest: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(id), 0) FROM direct_messages WHERE match_id = ?1 AND id <= COALESCE(?2, id)",
+            rusqlite::params![match_id, requested],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    // Read state only moves forward
+    conn.execute(
+        "INSERT INTO dm_read_state (match_id, user_id, last_read_message_id, updated_at) VALUES (?1, ?2, ?3, datetime('now'))
+         ON CONFLICT(match_id, user_id) DO UPDATE SET
+         last_read_message_id = MAX(last_read_message_id, ?3), updated_at = datetime('now')",
+        rusqlite::params![match_id, &claims.sub, latest],
+    ).unwrap();
+
+    let last_read = last_read_message_id(&conn, match_id, &claims.sub);
+    publish_to_match(
+        broker.get_ref(),
+        &participants,
+        RealtimeEvent::Read {
+            match_id,
+            user_id: claims.sub.clone(),
+            last_read_message_id: last_read,
+        },
+    );
+
+    HttpResponse::Ok().json(serde_json::json!({"last_read_message_id": last_read}))
 }
 
 pub async fn send_direct_message(
     req: HttpRequest,
     db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    moderator: web::Data<dyn Moderator>,
     path: web::Path<i64>,
     body: web::Json<SendDirectMessageRequest>,
 ) -> HttpResponse {
@@ -654,34 +1061,355 @@ pub async fn send_direct_message(
         return HttpResponse::BadRequest().json(serde_json::json!({"error": "Message cannot be empty"}));
     }
 
+    {
+        let conn = db.conn.lock().unwrap();
+        if match_participants(&conn, match_id, &claims.sub).is_none() {
+            return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}));
+        }
+    }
+
+    let verdict = match screen(moderator.get_ref(), &db, &claims.sub, ModerationContext::DirectMessage, Some(match_id), &content).await {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
+
     let conn = db.conn.lock().unwrap();
 
     // Verify user is part of this match
-    let is_participant: bool = conn
+    let participants = match match_participants(&conn, match_id, &claims.sub) {
+        Some(p) => p,
+        None => return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"})),
+    };
+
+    conn.execute(
+        "INSERT INTO direct_messages (match_id, sender_id, content) VALUES (?1, ?2, ?3)",
+        rusqlite::params![match_id, &claims.sub, &content],
+    ).unwrap();
+    record_flag(&conn, &claims.sub, ModerationContext::DirectMessage, Some(match_id), Some(conn.last_insert_rowid()), &content, &verdict);
+
+    let msg = match get_direct_message_db(&conn, match_id, conn.last_insert_rowid()) {
+        Some(m) => m,
+        None => return HttpResponse::InternalServerError().json(serde_json::json!({"error": "Failed to send message"})),
+    };
+
+    publish_to_match(broker.get_ref(), &participants, RealtimeEvent::Message { message: msg.clone() });
+
+    HttpResponse::Ok().json(msg)
+}
+
+/// Draft replies with the caller's own agent. Nothing is sent or stored — the user picks,
+/// edits and sends a suggestion themselves.
+pub async fn suggest_replies(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    agent: web::Data<LlmAgent>,
+    path: web::Path<i64>,
+    body: Option<web::Json<SuggestRepliesRequest>>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let match_id = path.into_inner();
+    let hint = body.and_then(|b| b.into_inner().hint).map(|h| h.trim().to_string()).filter(|h| !h.is_empty());
+
+    let (my_profile, their_name, thread, known_names) = {
+        let conn = db.conn.lock().unwrap();
+        let (a, b) = match match_participants(&conn, match_id, &claims.sub) {
+            Some(p) => p,
+            None => return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"})),
+        };
+        let other_id = if a == claims.sub { b } else { a };
+
+        // Only the other person's public display name — never their agent profile
+        let their_name: String = conn
+            .query_row("SELECT display_name FROM users WHERE id = ?1", rusqlite::params![&other_id], |row| row.get(0))
+            .unwrap_or_else(|_| "Your match".to_string());
+
+        let mut stmt = conn
+            .prepare("SELECT sender_id, content FROM direct_messages WHERE match_id = ?1 AND deleted_at IS NULL ORDER BY id DESC LIMIT 20")
+            .unwrap();
+        let mut thread: Vec<(bool, String)> = stmt
+            .query_map(rusqlite::params![match_id], |row| {
+                Ok((row.get::<_, String>(0)? == claims.sub, row.get::<_, String>(1)?))
+            })
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .filter(|(_, content)| !content.is_empty())
+            .collect();
+        thread.reverse();
+
+        let mut known_names = known_names_db(&conn, &claims.sub);
+        known_names.extend(known_names_db(&conn, &other_id));
+
+        (get_agent_profile_db(&conn, &claims.sub), their_name, thread, known_names)
+    };
+
+    match agent.suggest_replies(&my_profile, &their_name, &thread, hint.as_deref(), &known_names).await {
+        Ok(suggestions) => HttpResponse::Ok().json(ReplySuggestions { suggestions }),
+        Err(e) => {
+            log::error!("Reply suggestions failed for {}: {}", claims.sub, e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "Could not draft suggestions right now"}))
+        }
+    }
+}
+
+/// The caller's own message in a match they're part of, if it can still be edited
+fn editable_message(
+    conn: &rusqlite::Connection,
+    match_id: i64,
+    message_id: i64,
+    user_id: &str,
+) -> Result<((String, String), DirectMessage), HttpResponse> {
+    let participants = match match_participants(conn, match_id, user_id) {
+        Some(p) => p,
+        None => return Err(HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}))),
+    };
+
+    let existing = match get_direct_message_db(conn, match_id, message_id) {
+        Some(m) => m,
+        None => return Err(HttpResponse::NotFound().json(serde_json::json!({"error": "Message not found"}))),
+    };
+
+    if existing.sender_id != user_id {
+        return Err(HttpResponse::Forbidden().json(serde_json::json!({"error": "You can only edit your own messages"})));
+    }
+    if existing.is_deleted {
+        return Err(HttpResponse::BadRequest().json(serde_json::json!({"error": "Message was deleted"})));
+    }
+
+    let window = dm_edit_window_minutes();
+    let within_window: bool = conn
         .query_row(
-            "SELECT COUNT(*) FROM matches WHERE id = ?1 AND is_matched = 1 AND (user_a_id = ?2 OR user_b_id = ?2)",
-            rusqlite::params![match_id, &claims.sub],
-            |row| row.get::<_, i64>(0),
+            "SELECT created_at >= datetime('now', ?2) FROM direct_messages WHERE id = ?1",
+            rusqlite::params![message_id, format!("-{} minutes", window)],
+            |row| row.get(0),
         )
-        .unwrap_or(0)
-        > 0;
+        .unwrap_or(false);
 
-    if !is_participant {
+    if !within_window {
+        return Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Messages can only be edited within {} minutes of sending", window)
+        })));
+    }
+
+    Ok((participants, existing))
+}
+
+pub async fn edit_direct_message(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    moderator: web::Data<dyn Moderator>,
+    path: web::Path<(i64, i64)>,
+    body: web::Json<SendDirectMessageRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (match_id, message_id) = path.into_inner();
+    let content = body.content.trim().to_string();
+
+    if content.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "Message cannot be empty"}));
+    }
+
+    if let Err(e) = editable_message(&db.conn.lock().unwrap(), match_id, message_id, &claims.sub) {
+        return e;
+    }
+
+    // Edits go through the same screening as new messages
+    let verdict = match screen(moderator.get_ref(), &db, &claims.sub, ModerationContext::DirectMessage, Some(match_id), &content).await {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
+
+    // Checked again: the message may have been deleted or aged out while screening ran
+    let conn = db.conn.lock().unwrap();
+    let (participants, existing) = match editable_message(&conn, match_id, message_id, &claims.sub) {
+        Ok(m) => m,
+        Err(e) => return e,
+    };
+
+    if existing.content != content {
+        conn.execute(
+            "INSERT INTO dm_edits (message_id, previous_content) VALUES (?1, ?2)",
+            rusqlite::params![message_id, &existing.content],
+        ).unwrap();
+        conn.execute(
+            "UPDATE direct_messages SET content = ?1, edited_at = datetime('now') WHERE id = ?2",
+            rusqlite::params![&content, message_id],
+        ).unwrap();
+        record_flag(&conn, &claims.sub, ModerationContext::DirectMessage, Some(match_id), Some(message_id), &content, &verdict);
+    }
+
+    let msg = get_direct_message_db(&conn, match_id, message_id).unwrap_or(existing);
+    publish_to_match(broker.get_ref(), &participants, RealtimeEvent::MessageUpdated { message: msg.clone() });
+
+    HttpResponse::Ok().json(msg)
+}
+
+pub async fn delete_direct_message(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    blobs: web::Data<dyn BlobStore>,
+    path: web::Path<(i64, i64)>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (match_id, message_id) = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let participants = match match_participants(&conn, match_id, &claims.sub) {
+        Some(p) => p,
+        None => return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"})),
+    };
+
+    let existing = match get_direct_message_db(&conn, match_id, message_id) {
+        Some(m) => m,
+        None => return HttpResponse::NotFound().json(serde_json::json!({"error": "Message not found"})),
+    };
+
+    if existing.sender_id != claims.sub {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": "You can only delete your own messages"}));
+    }
+
+    // Attachments go with the message; nobody can fetch them once it is deleted
+    let blob_keys = detach_message_attachments(&conn, "id = ?1", &message_id).unwrap();
+    conn.execute(
+        "UPDATE direct_messages SET deleted_at = COALESCE(deleted_at, datetime('now')) WHERE id = ?1",
+        rusqlite::params![message_id],
+    ).unwrap();
+    remove_blobs(blobs.get_ref(), &blob_keys);
+
+    let msg = get_direct_message_db(&conn, match_id, message_id).unwrap_or(existing);
+    publish_to_match(broker.get_ref(), &participants, RealtimeEvent::MessageUpdated { message: msg.clone() });
+
+    HttpResponse::Ok().json(msg)
+}
+
+pub async fn get_direct_message_history(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    path: web::Path<(i64, i64)>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (match_id, message_id) = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    if match_participants(&conn, match_id, &claims.sub).is_none() {
         return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"}));
     }
 
+    // Deleting a message also withdraws its earlier versions
+    let msg = match get_direct_message_db(&conn, match_id, message_id) {
+        Some(m) if !m.is_deleted => m,
+        _ => return HttpResponse::NotFound().json(serde_json::json!({"error": "Message not found"})),
+    };
+
+    // Earlier versions are the sender's to look back on; the other person only sees the current text
+    if msg.sender_id != claims.sub {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": "You can only see the history of your own messages"}));
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT previous_content, edited_at FROM dm_edits WHERE message_id = ?1 ORDER BY id ASC")
+        .unwrap();
+    let edits: Vec<DmEdit> = stmt
+        .query_map(rusqlite::params![message_id], |row| {
+            Ok(DmEdit {
+                previous_content: row.get(0)?,
+                edited_at: row.get(1)?,
+            })
+        })
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+
+    HttpResponse::Ok().json(edits)
+}
+
+pub async fn add_reaction(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    path: web::Path<(i64, i64)>,
+    body: web::Json<ReactionRequest>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (match_id, message_id) = path.into_inner();
+    let emoji = body.emoji.trim().to_string();
+
+    // Reactions are emoji, not free text
+    if !is_emoji(&emoji) {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "Reaction must be an emoji"}));
+    }
+
+    let conn = db.conn.lock().unwrap();
+    let participants = match match_participants(&conn, match_id, &claims.sub) {
+        Some(p) => p,
+        None => return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"})),
+    };
+
+    match get_direct_message_db(&conn, match_id, message_id) {
+        Some(m) if !m.is_deleted => {}
+        _ => return HttpResponse::NotFound().json(serde_json::json!({"error": "Message not found"})),
+    }
+
     conn.execute(
-        "INSERT INTO direct_messages (match_id, sender_id, content) VALUES (?1, ?2, ?3)",
-        rusqlite::params![match_id, &claims.sub, &content],
+        "INSERT OR IGNORE INTO dm_reactions (message_id, user_id, emoji) VALUES (?1, ?2, ?3)",
+        rusqlite::params![message_id, &claims.sub, &emoji],
     ).unwrap();
 
-    let msg = DirectMessage {
-        id: conn.last_insert_rowid(),
-        match_id,
-        sender_id: claims.sub,
-        content,
-        created_at: chrono::Utc::now().to_rfc3339(),
+    let msg = get_direct_message_db(&conn, match_id, message_id).unwrap();
+    publish_to_match(broker.get_ref(), &participants, RealtimeEvent::MessageUpdated { message: msg.clone() });
+
+    HttpResponse::Ok().json(msg)
+}
+
+pub async fn remove_reaction(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    broker: web::Data<dyn Broker>,
+    path: web::Path<(i64, i64, String)>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (match_id, message_id, emoji) = path.into_inner();
+    let conn = db.conn.lock().unwrap();
+    let participants = match match_participants(&conn, match_id, &claims.sub) {
+        Some(p) => p,
+        None => return HttpResponse::Forbidden().json(serde_json::json!({"error": "Not authorized"})),
     };
 
+    match get_direct_message_db(&conn, match_id, message_id) {
+        Some(m) if !m.is_deleted => {}
+        _ => return HttpResponse::NotFound().json(serde_json::json!({"error": "Message not found"})),
+    }
+
+    conn.execute(
+        "DELETE FROM dm_reactions WHERE message_id = ?1 AND user_id = ?2 AND emoji = ?3",
+        rusqlite::params![message_id, &claims.sub, &emoji],
+    ).unwrap();
+
+    let msg = get_direct_message_db(&conn, match_id, message_id).unwrap();
+    publish_to_match(broker.get_ref(), &participants, RealtimeEvent::MessageUpdated { message: msg.clone() });
+
     HttpResponse::Ok().json(msg)
 }
@@ -0,0 +1,58 @@
+use std::io;
+use std::path::PathBuf;
+
+/// Where attachment bytes live. Handlers only talk to this trait, so the local-filesystem
+/// backend can later be swapped for S3, GCS, etc.
+pub trait BlobStore: Send + Sync {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    /// Removing a blob that doesn't exist is not an error
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Stores each blob as a file under `root`, fanned out by the first two characters of its key
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(LocalBlobStore { root })
+    }
+
+    fn path_for(&self, key: &str) -> io::Result<PathBuf> {
+        // Keys are generated server-side, but never let one escape the root
+        if key.len() < 3 || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid blob key"));
+        }
+        Ok(self.root.join(&key[..2]).join(key))
+    }
+}
+
+impl BlobStore for LocalBlobStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path_for(key)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write to a temp file first so readers never see a half-written blob
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(&tmp, &path)
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.path_for(key)?)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match std::fs::remove_file(self.path_for(key)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
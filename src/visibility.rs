@@ -0,0 +1,233 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::agent::LlmAgent;
+use crate::auth::extract_user_id;
+use crate::db::Database;
+use crate::models::*;
+use crate::redaction::redact_known_names;
+use crate::routes::get_agent_profile_db;
+
+/// How to reach one profile field, and its visibility setting, by name
+struct ProfileFieldAccess {
+    name: &'static str,
+    value: fn(&AgentProfile) -> &String,
+    value_mut: fn(&mut AgentProfile) -> &mut String,
+    visibility: fn(&ProfileVisibility) -> &String,
+    visibility_mut: fn(&mut ProfileVisibility) -> &mut String,
+}
+
+/// The one list of profile fields; everything else that works field by field goes through it
+macro_rules! profile_fields {
+    ($($field:ident),* $(,)?) => {
+        pub const PROFILE_FIELDS: &[&str] = &[$(stringify!($field)),*];
+
+        const FIELD_ACCESS: &[ProfileFieldAccess] = &[$(ProfileFieldAccess {
+            name: stringify!($field),
+            value: |p| &p.$field,
+            value_mut: |p| &mut p.$field,
+            visibility: |v| &v.$field,
+            visibility_mut: |v| &mut v.$field,
+        }),*];
+    };
+}
+
+profile_fields!(
+    personality_summary,
+    interests,
+    core_values,
+    communication_style,
+    looking_for,
+    deal_breakers,
+    raw_notes,
+);
+
+fn field_access(field: &str) -> Option<&'static ProfileFieldAccess> {
+    FIELD_ACCESS.iter().find(|f| f.name == field)
+}
+
+const VISIBILITIES: &[&str] = &["private", "agent_only", "shareable"];
+
+/// Who a filtered profile is being prepared for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience {
+    /// The user's own agent, evaluating others on their behalf
+    OwnAgent,
+    /// Another user's agent
+    OtherAgent,
+}
+
+fn visibility_slot<'a>(visibility: &'a mut ProfileVisibility, field: &str) -> Option<&'a mut String> {
+    field_access(field).map(|f| (f.visibility_mut)(visibility))
+}
+
+pub fn visibility_of<'a>(visibility: &'a ProfileVisibility, field: &str) -> &'a str {
+    field_access(field).map_or("private", |f| (f.visibility)(visibility))
+}
+
+pub fn profile_field_mut<'a>(profile: &'a mut AgentProfile, field: &str) -> Option<&'a mut String> {
+    field_access(field).map(|f| (f.value_mut)(profile))
+}
+
+pub fn profile_field<'a>(profile: &'a AgentProfile, field: &str) -> &'a str {
+    field_access(field).map_or("", |f| (f.value)(profile))
+}
+
+/// The user's settings, with defaults for any field they haven't touched
+pub fn get_visibility_db(conn: &rusqlite::Connection, user_id: &str) -> ProfileVisibility {
+    let mut visibility = ProfileVisibility::default();
+    let mut stmt = conn
+        .prepare("SELECT field, visibility FROM agent_profile_visibility WHERE user_id = ?1")
+        .unwrap();
+    let rows: Vec<(String, String)> = stmt
+        .query_map(rusqlite::params![user_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect();
+    for (field, value) in rows {
+        if let Some(slot) = visibility_slot(&mut visibility, &field) {
+            *slot = value;
+        }
+    }
+    visibility
+}
+
+/// Blank out every field the audience isn't allowed to see
+pub fn filter_profile(profile: &AgentProfile, visibility: &ProfileVisibility, audience: Audience) -> AgentProfile {
+    let mut filtered = profile.clone();
+    for field in PROFILE_FIELDS {
+        let allowed = matches!(
+            (visibility_of(visibility, field), audience),
+            ("shareable", _) | ("agent_only", Audience::OwnAgent)
+        );
+        if !allowed && let Some(value) = profile_field_mut(&mut filtered, field) {
+            value.clear();
+        }
+    }
+    filtered
+}
+
+/// Names a user is known by: their display name and any full name they introduced
+/// themselves with in chat. Profiles are stored with real names so the user sees them.
+pub fn known_names_db(conn: &rusqlite::Connection, user_id: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT display_name FROM users WHERE id = ?1 AND display_name != ''
+             UNION ALL SELECT name FROM known_names WHERE user_id = ?1",
+        )
+        .unwrap();
+    stmt.query_map(rusqlite::params![user_id], |row| row.get(0))
+        .unwrap()
+        .filter_map(|r| r.ok())
+        .collect()
+}
+
+/// A user's profile as the given audience may see it. Other agents also get the user's
+/// names replaced, since those are only caught at call time when an introduction is in the prompt.
+pub fn profile_for(conn: &rusqlite::Connection, user_id: &str, audience: Audience) -> AgentProfile {
+    let mut profile = filter_profile(&get_agent_profile_db(conn, user_id), &get_visibility_db(conn, user_id), audience);
+    if audience == Audience::OtherAgent {
+        let names = known_names_db(conn, user_id);
+        for field in PROFILE_FIELDS {
+            if let Some(value) = profile_field_mut(&mut profile, field) {
+                *value = redact_known_names(value, &names);
+            }
+        }
+    }
+    profile
+}
+
+// ── Handlers ──
+
+pub async fn get_profile_visibility(
+    req: HttpRequest,
+    db: web::Data<Database>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let conn = db.conn.lock().unwrap();
+    HttpResponse::Ok().json(get_visibility_db(&conn, &claims.sub))
+}
+
+pub async fn update_profile_visibility(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    body: web::Json<HashMap<String, String>>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    for (field, value) in body.iter() {
+        if !PROFILE_FIELDS.contains(&field.as_str()) {
+            return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Unknown profile field: {}", field)}));
+        }
+        if !VISIBILITIES.contains(&value.as_str()) {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("{} must be private, agent_only or shareable", field)
+            }));
+        }
+    }
+
+    let conn = db.conn.lock().unwrap();
+    for (field, value) in body.iter() {
+        conn.execute(
+            "INSERT INTO agent_profile_visibility (user_id, field, visibility) VALUES (?1, ?2, ?3)
+             ON CONFLICT(user_id, field) DO UPDATE SET visibility = ?3, updated_at = datetime('now')",
+            rusqlite::params![&claims.sub, field, value],
+        ).unwrap();
+    }
+
+    HttpResponse::Ok().json(get_visibility_db(&conn, &claims.sub))
+}
+
+pub async fn get_profile_disclosure(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    agent: web::Data<LlmAgent>,
+) -> HttpResponse {
+    let claims = match extract_user_id(&req) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let (profile, visibility, for_others, known_names) = {
+        let conn = db.conn.lock().unwrap();
+        (
+            get_agent_profile_db(&conn, &claims.sub),
+            get_visibility_db(&conn, &claims.sub),
+            profile_for(&conn, &claims.sub, Audience::OtherAgent),
+            known_names_db(&conn, &claims.sub),
+        )
+    };
+
+    let mut shared = BTreeMap::new();
+    let mut agent_only = BTreeMap::new();
+    let mut private = Vec::new();
+    for field in PROFILE_FIELDS {
+        let value = profile_field(&profile, field).to_string();
+        match visibility_of(&visibility, field) {
+            "shareable" => {
+                shared.insert(field.to_string(), value);
+            }
+            "agent_only" => {
+                agent_only.insert(field.to_string(), value);
+            }
+            _ => private.push(field.to_string()),
+        }
+    }
+
+    let prompt_preview = agent.disclosure_preview(&for_others, &known_names);
+
+    HttpResponse::Ok().json(ProfileDisclosure {
+        visibility,
+        shared,
+        agent_only,
+        private,
+        prompt_preview,
+    })
+}